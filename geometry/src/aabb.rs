@@ -1,7 +1,4 @@
-use util::{
-    HitResult, Interval, Ray, Vec3,
-    quat::{from_axis_angle, quat_rotate},
-};
+use util::{HitResult, Interval, Point, Ray, Vec3};
 
 use crate::{
     bounds::{Axis, Bounds},
@@ -76,6 +73,16 @@ impl AABB {
         bounds
     }
 
+    fn recompute_bounds(&mut self) {
+        self.bounds = match &self.aabb_type {
+            AABBType::Recursive(c) => Bounds {
+                min: Point::min(&c.left.bounds.min, &c.right.bounds.min),
+                max: Point::max(&c.left.bounds.max, &c.right.bounds.max),
+            },
+            AABBType::Leaf(children) => Self::calc_bounds(children),
+        };
+    }
+
     pub fn debug_hit_count(&self, ray: &Ray, interval: &Interval) -> u32 {
         if self.bounds.hit(ray, interval).is_none() {
             return 0;
@@ -158,10 +165,8 @@ impl Hittable for AABB {
         }
     }
 
+    // Negative scales and rotations can swap the corners, so bounds are rebuilt from the children
     fn scale(&mut self, vec: &Vec3) {
-        self.bounds.min = self.bounds.min * *vec;
-        self.bounds.max = self.bounds.max * *vec;
-
         match &mut self.aabb_type {
             AABBType::Recursive(c) => {
                 c.left.scale(vec);
@@ -173,13 +178,11 @@ impl Hittable for AABB {
                 }
             }
         }
+
+        self.recompute_bounds();
     }
 
     fn rotate(&mut self, axis: &Vec3, angle_rad: f32) {
-        let quat = from_axis_angle(*axis, angle_rad);
-        self.bounds.min = quat_rotate(quat, self.bounds.min);
-        self.bounds.max = quat_rotate(quat, self.bounds.max);
-
         match &mut self.aabb_type {
             AABBType::Recursive(c) => {
                 c.left.rotate(axis, angle_rad);
@@ -191,6 +194,8 @@ impl Hittable for AABB {
                 }
             }
         }
+
+        self.recompute_bounds();
     }
 }
 
@@ -32,6 +32,7 @@ impl Bounds {
         }
     }
 
+    // Inclusive so flat boxes around axis-aligned triangles can still be hit
     pub fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Interval> {
         let mut t_min = interval.min;
         let mut t_max = interval.max;
@@ -44,7 +45,7 @@ impl Bounds {
         }
         t_min = t_min.max(t0);
         t_max = t_max.min(t1);
-        if t_max <= t_min {
+        if t_max < t_min {
             return None;
         }
 
@@ -56,7 +57,7 @@ impl Bounds {
         }
         t_min = t_min.max(t0);
         t_max = t_max.min(t1);
-        if t_max <= t_min {
+        if t_max < t_min {
             return None;
         }
 
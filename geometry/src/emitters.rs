@@ -0,0 +1,89 @@
+use crate::{
+    aabb::{AABB, AABBType},
+    hittable::HittableType,
+    transpose::mat4_multiply,
+};
+
+const IDENTITY: [[f64; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+impl HittableType {
+    // Flattens every primitive with an emissive material into world-space copies
+    pub fn collect_emitters<F: Fn(usize) -> bool>(
+        &self,
+        is_emissive: &F,
+        emitters: &mut Vec<HittableType>,
+    ) {
+        self.collect_transformed_emitters(IDENTITY, is_emissive, emitters);
+    }
+
+    fn collect_transformed_emitters<F: Fn(usize) -> bool>(
+        &self,
+        object_to_world: [[f64; 4]; 4],
+        is_emissive: &F,
+        emitters: &mut Vec<HittableType>,
+    ) {
+        match self {
+            HittableType::Sphere(sphere) => {
+                if sphere.material_index.is_some_and(is_emissive) {
+                    emitters.push(HittableType::Sphere(sphere.transformed(object_to_world)));
+                }
+            }
+            HittableType::Tri(tri) => {
+                if tri.material_index().is_some_and(is_emissive) {
+                    emitters.push(HittableType::Tri(tri.transformed(object_to_world)));
+                }
+            }
+            HittableType::Mesh(mesh) => {
+                mesh.aabb
+                    .collect_transformed_emitters(object_to_world, is_emissive, emitters);
+            }
+            HittableType::Instance(instance) => instance.base.collect_transformed_emitters(
+                mat4_multiply(object_to_world, instance.object_to_world),
+                is_emissive,
+                emitters,
+            ),
+            HittableType::Parent(parent) => {
+                let object_to_world = mat4_multiply(object_to_world, parent.object_to_world);
+                for child in &parent.children {
+                    child.collect_transformed_emitters(object_to_world, is_emissive, emitters);
+                }
+            }
+        }
+    }
+}
+
+impl AABB {
+    pub fn collect_emitters<F: Fn(usize) -> bool>(
+        &self,
+        is_emissive: &F,
+        emitters: &mut Vec<HittableType>,
+    ) {
+        self.collect_transformed_emitters(IDENTITY, is_emissive, emitters);
+    }
+
+    fn collect_transformed_emitters<F: Fn(usize) -> bool>(
+        &self,
+        object_to_world: [[f64; 4]; 4],
+        is_emissive: &F,
+        emitters: &mut Vec<HittableType>,
+    ) {
+        match &self.aabb_type {
+            AABBType::Recursive(c) => {
+                c.left
+                    .collect_transformed_emitters(object_to_world, is_emissive, emitters);
+                c.right
+                    .collect_transformed_emitters(object_to_world, is_emissive, emitters);
+            }
+            AABBType::Leaf(children) => {
+                for child in children {
+                    child.collect_transformed_emitters(object_to_world, is_emissive, emitters);
+                }
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use gltf::{GltfData, GltfMesh};
 use util::{HitResult, Interval, Ray, Vec3};
 
@@ -5,6 +7,14 @@ use crate::{
     bounds::Bounds, instance::Instance, mesh::Mesh, parent::Parent, sphere::Sphere, tri::Tri,
 };
 
+static NEXT_OBJECT_ID: AtomicU64 = AtomicU64::new(1);
+
+// Unique id for a closed object, so overlapping copies of one material can be told apart.
+// Loose triangles are 0
+pub(crate) fn next_object_id() -> u64 {
+    NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
 #[allow(dead_code)]
 pub trait Hittable {
     fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitResult>;
@@ -1,17 +1,14 @@
 use std::sync::Arc;
 
 use gltf::Node;
-use util::{
-    HitResult, Interval, Ray, Vec3,
-    quat::{from_axis_angle, quat_multiply},
-};
+use util::{HitResult, Interval, Ray, Vec3, quat::from_axis_angle};
 
 use crate::{
     bounds::Bounds,
-    hittable::{Hittable, HittableType},
+    hittable::{Hittable, HittableType, next_object_id},
     transpose::{
-        mat3_inverse_transpose, mat4_inverse, mat4_transform_dir, mat4_transform_point,
-        transform_bounds_with_matrix, trs_matrix,
+        mat3_inverse_transpose, mat4_inverse, mat4_multiply, mat4_transform_dir,
+        mat4_transform_point, transform_bounds_with_matrix, trs_matrix,
     },
 };
 
@@ -19,14 +16,12 @@ use crate::{
 #[allow(dead_code)]
 pub struct Instance {
     pub name: String,
-    pub translation: Option<Vec3>,
-    pub rotation: Option<[f32; 4]>,
-    pub scale: Vec3,
     pub world_to_object: [[f64; 4]; 4], // inverse TRS
     pub object_to_world: [[f64; 4]; 4], // TRS
     normal_matrix: [[f64; 4]; 4],
     bounds: Bounds, // World Space
     pub base: Arc<HittableType>,
+    id: u64, // Instances of the same base are separate objects
 }
 
 impl Instance {
@@ -47,14 +42,12 @@ impl Instance {
 
         Self {
             name,
-            translation,
-            rotation,
-            scale,
             object_to_world,
             world_to_object,
             normal_matrix,
             bounds: transform_bounds_with_matrix(base.get_bounds(), object_to_world),
             base,
+            id: next_object_id(),
         }
     }
 }
@@ -89,15 +82,21 @@ impl Hittable for Instance {
             max: interval.max * dir_length,
         };
 
-        let mut hit = self
-            .base
-            .hit(&transformed_ray, &transformed_interval)?;
+        let mut hit = self.base.hit(&transformed_ray, &transformed_interval)?;
 
         // t is in object space with normalized dir, scale back to world space
         hit.t /= dir_length;
+        hit.object_id = self.id;
 
         hit.point = mat4_transform_point(self.object_to_world, hit.point);
         hit.normal = mat4_transform_dir(self.normal_matrix, &hit.normal).normalize();
+        for tex_coord in &mut hit.tex_coords {
+            let (du, dv) = tex_coord.gradients;
+            tex_coord.gradients = (
+                mat4_transform_dir(self.normal_matrix, &du),
+                mat4_transform_dir(self.normal_matrix, &dv),
+            );
+        }
         Some(hit)
     }
 
@@ -106,27 +105,16 @@ impl Hittable for Instance {
     }
 
     fn translate(&mut self, vec: &Vec3) {
-        match self.translation {
-            Some(t) => self.translation = Some(t + vec),
-            None => self.translation = Some(*vec),
-        }
-
-        self.recompute_bounds();
+        self.apply_transform(trs_matrix(Some(*vec), None, Vec3::from(1.0)));
     }
 
     fn scale(&mut self, vec: &Vec3) {
-        self.scale = self.scale * *vec;
-        self.recompute_bounds();
+        self.apply_transform(trs_matrix(None, None, *vec));
     }
 
     fn rotate(&mut self, axis: &Vec3, angle_rad: f32) {
-        let rot = from_axis_angle(*axis, angle_rad);
-        self.rotation = match self.rotation {
-            Some(r) => Some(quat_multiply(rot, r)),
-            None => Some(rot),
-        };
-
-        self.recompute_bounds();
+        let rotation = from_axis_angle(*axis, angle_rad);
+        self.apply_transform(trs_matrix(None, Some(rotation), Vec3::from(1.0)));
     }
 }
 
@@ -172,9 +160,9 @@ impl TryFrom<(&[Arc<HittableType>], Node)> for Instance {
 }
 
 impl Instance {
-    #[allow(dead_code)]
-    fn recompute_bounds(&mut self) {
-        self.object_to_world = trs_matrix(self.translation, self.rotation, self.scale);
+    // Applies inc in world space, so the node's translation moves along with it like lights do
+    fn apply_transform(&mut self, inc: [[f64; 4]; 4]) {
+        self.object_to_world = mat4_multiply(inc, self.object_to_world);
         self.world_to_object = mat4_inverse(self.object_to_world);
         self.normal_matrix = mat3_inverse_transpose(self.object_to_world);
         self.bounds = transform_bounds_with_matrix(self.base.get_bounds(), self.object_to_world);
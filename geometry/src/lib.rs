@@ -1,5 +1,6 @@
 mod aabb;
 mod bounds;
+mod emitters;
 mod hittable;
 mod instance;
 mod mesh;
@@ -15,4 +16,5 @@ pub use instance::Instance;
 pub use mesh::Mesh;
 pub use parent::Parent;
 pub use sphere::Sphere;
+pub use transpose::{mat4_multiply, mat4_transform_dir, mat4_transform_point, trs_matrix};
 pub use tri::Tri;
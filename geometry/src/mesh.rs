@@ -4,19 +4,23 @@ use util::{HitResult, Interval, Ray, Vec3};
 use crate::{
     aabb::AABB,
     bounds::Bounds,
-    hittable::{Hittable, HittableType},
+    hittable::{Hittable, HittableType, next_object_id},
     tri::Tri,
 };
 
 #[derive(Debug)]
 pub struct Mesh {
     pub aabb: AABB,
+    id: u64,
 }
 
 impl Mesh {
     pub fn new(children: Vec<HittableType>) -> Self {
         let aabb = AABB::new(children);
-        Mesh { aabb }
+        Mesh {
+            aabb,
+            id: next_object_id(),
+        }
     }
 
     #[allow(clippy::cast_sign_loss)]
@@ -68,6 +72,13 @@ impl Mesh {
             let AccessorData::Vec2(uvs) = uv_accessor.get_data(gltf_data, binary) else {
                 panic!("expected Vec2")
             };
+            let second_uvs = primitive.attributes.texcoord_1.map(|index| {
+                let uv_accessor = gltf_data.accessors.get(index).unwrap();
+                let AccessorData::Vec2(uvs) = uv_accessor.get_data(gltf_data, binary) else {
+                    panic!("expected Vec2")
+                };
+                uvs
+            });
 
             let index_accessor = gltf_data.accessors.get(primitive.indices).unwrap();
             let indices: Vec<usize> = match index_accessor.get_data(gltf_data, binary) {
@@ -84,6 +95,9 @@ impl Mesh {
                 let uvb = uvs[tri[1]].into();
                 let uvc = uvs[tri[2]].into();
                 let uvs = Some((uva, uvb, uvc));
+                let second_uvs = second_uvs
+                    .as_ref()
+                    .map(|uvs| (uvs[tri[0]].into(), uvs[tri[1]].into(), uvs[tri[2]].into()));
 
                 let na = Vec3::from(normals[tri[0]]).normalize();
                 let nb = Vec3::from(normals[tri[1]]).normalize();
@@ -108,7 +122,8 @@ impl Mesh {
                     uvs,
                     tan,
                     primitive.material.map(|m| m + mat_offset),
-                );
+                )
+                .with_second_uvs(second_uvs);
 
                 // Check for degen triangles
                 if tri.edge_ab.length_squared() < 1e-12 || tri.edge_ac.length_squared() < 1e-12 {
@@ -125,7 +140,9 @@ impl Mesh {
 
 impl Hittable for Mesh {
     fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitResult> {
-        self.aabb.hit(ray, interval)
+        let mut hit = self.aabb.hit(ray, interval)?;
+        hit.object_id = self.id;
+        Some(hit)
     }
 
     fn get_bounds(&self) -> &Bounds {
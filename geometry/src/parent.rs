@@ -49,8 +49,9 @@ impl Parent {
         }
     }
 
+    // Applies inc in world space, after the node's own transform, like transforms of meshes
     fn apply_transform(&mut self, inc: [[f64; 4]; 4]) {
-        self.object_to_world = mat4_multiply(self.object_to_world, inc); // swap order
+        self.object_to_world = mat4_multiply(inc, self.object_to_world);
         self.world_to_object = mat4_inverse(self.object_to_world);
         self.normal_matrix = mat3_inverse_transpose(self.object_to_world);
         self.bounds = transform_bounds_with_matrix(&self.local_bounds, self.object_to_world);
@@ -79,6 +80,13 @@ impl Hittable for Parent {
 
                 hit.point = mat4_transform_point(self.object_to_world, hit.point);
                 hit.normal = mat4_transform_dir(self.normal_matrix, &hit.normal).normalize();
+                for tex_coord in &mut hit.tex_coords {
+                    let (du, dv) = tex_coord.gradients;
+                    tex_coord.gradients = (
+                        mat4_transform_dir(self.normal_matrix, &du),
+                        mat4_transform_dir(self.normal_matrix, &dv),
+                    );
+                }
 
                 if closest_hit.is_none() || hit.t < closest_hit.as_ref().unwrap().t {
                     closest_hit = Some(hit);
@@ -94,46 +102,16 @@ impl Hittable for Parent {
     }
 
     fn translate(&mut self, vec: &Vec3) {
-        self.apply_transform([
-            [1.0, 0.0, 0.0, f64::from(vec.x)],
-            [0.0, 1.0, 0.0, f64::from(vec.y)],
-            [0.0, 0.0, 1.0, f64::from(vec.z)],
-            [0.0, 0.0, 0.0, 1.0],
-        ]);
+        self.apply_transform(trs_matrix(Some(*vec), None, Vec3::from(1.0)));
     }
 
     fn scale(&mut self, vec: &Vec3) {
-        self.apply_transform([
-            [f64::from(vec.x), 0.0, 0.0, 0.0],
-            [0.0, f64::from(vec.y), 0.0, 0.0],
-            [0.0, 0.0, f64::from(vec.z), 0.0],
-            [0.0, 0.0, 0.0, 1.0],
-        ]);
+        self.apply_transform(trs_matrix(None, None, *vec));
     }
 
     fn rotate(&mut self, axis: &Vec3, angle_rad: f32) {
-        let [qx, qy, qz, qw] = from_axis_angle(*axis, angle_rad).map(f64::from);
-        self.apply_transform([
-            [
-                1.0 - 2.0 * (qy * qy + qz * qz),
-                2.0 * (qx * qy - qz * qw),
-                2.0 * (qx * qz + qy * qw),
-                0.0,
-            ],
-            [
-                2.0 * (qx * qy + qz * qw),
-                1.0 - 2.0 * (qx * qx + qz * qz),
-                2.0 * (qy * qz - qx * qw),
-                0.0,
-            ],
-            [
-                2.0 * (qx * qz - qy * qw),
-                2.0 * (qy * qz + qx * qw),
-                1.0 - 2.0 * (qx * qx + qy * qy),
-                0.0,
-            ],
-            [0.0, 0.0, 0.0, 1.0],
-        ]);
+        let rotation = from_axis_angle(*axis, angle_rad);
+        self.apply_transform(trs_matrix(None, Some(rotation), Vec3::from(1.0)));
     }
 
     fn debug_hit_count(&self, ray: &util::Ray, interval: &util::Interval) -> u32 {
@@ -1,8 +1,14 @@
 #![allow(clippy::many_single_char_names)]
 
-use util::{HitResult, Interval, Point, Ray, Vec3};
+use std::f32::consts::PI;
 
-use crate::{bounds::Bounds, hittable::Hittable};
+use util::{HitResult, Interval, Normalized, Point, Ray, TexCoord, Vec3};
+
+use crate::{
+    bounds::Bounds,
+    hittable::{Hittable, next_object_id},
+    transpose::{mat4_transform_dir, mat4_transform_point},
+};
 
 #[derive(Debug)]
 pub struct Sphere {
@@ -10,6 +16,7 @@ pub struct Sphere {
     pub radius: f32,
     bounds: Bounds,
     pub material_index: Option<usize>,
+    id: u64,
 }
 
 impl Sphere {
@@ -26,8 +33,69 @@ impl Sphere {
             radius,
             bounds,
             material_index,
+            id: next_object_id(),
+        }
+    }
+}
+
+impl Sphere {
+    pub fn area(&self) -> f32 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    // Uniformly samples a point on the sphere's surface
+    pub fn sample(&self, u: (f32, f32)) -> HitResult {
+        let normal = Vec3::sample_unit_sphere(u);
+        let point = self.center + normal * self.radius;
+        let normal = normal.normalize();
+
+        HitResult {
+            normal,
+            tangent: None,
+            t: 0.0,
+            point,
+            material_index: self.material_index,
+            tex_coords: [self.tex_coord(&normal); 2],
+            front_face: true,
+            object_id: self.id,
+            footprint: (Point::zero(), Point::zero()),
         }
     }
+
+    // Equirectangular uv, with gradients clamped near the poles where u degenerates
+    fn tex_coord(&self, normal: &Vec3<Normalized>) -> TexCoord {
+        let ring = (normal.x * normal.x + normal.z * normal.z).max(1e-4);
+        let du = Point::new(-normal.z, 0.0, normal.x) / (ring * 2.0 * PI * self.radius);
+        let along_y = Point::new(0.0, 1.0, 0.0) - *normal * normal.y;
+        let dv = along_y / (-ring.sqrt() * PI * self.radius);
+        TexCoord {
+            u: 0.5 + normal.z.atan2(normal.x) / (2.0 * PI),
+            v: 0.5 - normal.y.asin() / PI,
+            gradients: (du, dv),
+        }
+    }
+
+    // Light sampling needs the world-space sphere, which a non-uniform scale would turn into an
+    // ellipsoid
+    pub(crate) fn transformed(&self, m: [[f64; 4]; 4]) -> Self {
+        let axis_scale = |axis: Vec3| mat4_transform_dir(m, &axis).length();
+        let scales = [
+            axis_scale(Vec3::new(1.0, 0.0, 0.0)),
+            axis_scale(Vec3::new(0.0, 1.0, 0.0)),
+            axis_scale(Vec3::new(0.0, 0.0, 1.0)),
+        ];
+        let scale = scales[0];
+        assert!(
+            scales.iter().all(|s| (s - scale).abs() <= 1e-4 * scale),
+            "Emissive spheres can't be scaled non-uniformly, got scales {scales:?}"
+        );
+
+        Sphere::new(
+            mat4_transform_point(m, self.center),
+            self.radius * scale,
+            self.material_index,
+        )
+    }
 }
 
 impl Hittable for Sphere {
@@ -53,8 +121,6 @@ impl Hittable for Sphere {
         let point = ray.at(t);
         let outward = (point - self.center) / self.radius;
 
-        let u = 0.5 + outward.z.atan2(outward.x) / (2.0 * std::f32::consts::PI);
-        let v = 0.5 - outward.y.asin() / std::f32::consts::PI;
         let normal = outward.normalize();
 
         Some(HitResult {
@@ -62,10 +128,11 @@ impl Hittable for Sphere {
             tangent: None, // TODO
             t,
             point,
-            u,
-            v,
             material_index: self.material_index,
+            tex_coords: [self.tex_coord(&normal); 2],
             front_face: ray.dir.dot(&normal) < 0.0,
+            object_id: self.id,
+            footprint: (Point::zero(), Point::zero()),
         })
     }
 
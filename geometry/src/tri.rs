@@ -1,11 +1,15 @@
 #![allow(clippy::similar_names, clippy::many_single_char_names)]
 
 use util::{
-    HitResult, Interval, Normalized, Point, Ray, Vec3,
+    HitResult, Interval, Normalized, Point, Ray, TexCoord, Vec3,
     quat::{self, quat_rotate},
 };
 
-use crate::{bounds::Bounds, hittable::Hittable};
+use crate::{
+    bounds::Bounds,
+    hittable::Hittable,
+    transpose::{mat3_inverse_transpose, mat4_transform_dir, mat4_transform_point},
+};
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -16,6 +20,7 @@ pub struct Tri {
 
     pub normals: Option<(Vec3<Normalized>, Vec3<Normalized>, Vec3<Normalized>)>,
     pub uvs: Option<(Vec3, Vec3, Vec3)>,
+    second_uvs: Option<Box<(Vec3, Vec3, Vec3)>>, // Boxed, few meshes have them
     tangents: Option<[[f32; 4]; 3]>,
     face_normal: Vec3,
     pub edge_ab: Vec3,
@@ -58,6 +63,7 @@ impl Tri {
             v2,
             normals,
             uvs,
+            second_uvs: None,
             tangents,
             face_normal,
             edge_ab,
@@ -67,6 +73,113 @@ impl Tri {
         }
     }
 
+    pub fn material_index(&self) -> Option<usize> {
+        self.material_index
+    }
+
+    pub fn area(&self) -> f32 {
+        self.face_normal.length() * 0.5
+    }
+
+    // Uniformly samples a point on the triangle's surface
+    pub fn sample(&self, u: (f32, f32)) -> HitResult {
+        let su0 = u.0.sqrt();
+        let bary_u = u.1 * su0;
+        let bary_v = 1.0 - su0;
+        let w = 1.0 - bary_u - bary_v;
+
+        HitResult {
+            normal: self.unit_face_normal(),
+            tangent: None,
+            t: 0.0,
+            point: self.v0 * w + self.v1 * bary_u + self.v2 * bary_v,
+            material_index: self.material_index,
+            tex_coords: self.tex_coords(bary_u, bary_v),
+            front_face: true,
+            object_id: 0,
+            footprint: (Point::zero(), Point::zero()),
+        }
+    }
+
+    // Adds the TEXCOORD_1 set, for textures that ask for it
+    #[must_use]
+    pub fn with_second_uvs(mut self, uvs: Option<(Vec3, Vec3, Vec3)>) -> Self {
+        self.second_uvs = uvs.map(Box::new);
+        self
+    }
+
+    // Copy of the triangle with its vertices moved by an object-to-world matrix
+    pub(crate) fn transformed(&self, m: [[f64; 4]; 4]) -> Self {
+        let normal_matrix = mat3_inverse_transpose(m);
+        let normals = self.normals.map(|(n0, n1, n2)| {
+            (
+                mat4_transform_dir(normal_matrix, &n0).normalize(),
+                mat4_transform_dir(normal_matrix, &n1).normalize(),
+                mat4_transform_dir(normal_matrix, &n2).normalize(),
+            )
+        });
+
+        Tri::new(
+            mat4_transform_point(m, self.v0),
+            mat4_transform_point(m, self.v1),
+            mat4_transform_point(m, self.v2),
+            normals,
+            self.uvs,
+            self.tangents,
+            self.material_index,
+        )
+        .with_second_uvs(self.second_uvs.as_deref().copied())
+    }
+
+    // Small triangles have a face normal too short for normalize() to trust, so scale it first
+    fn unit_face_normal(&self) -> Vec3<Normalized> {
+        (self.face_normal / self.face_normal.length()).normalize()
+    }
+
+    fn tex_coords(&self, bary_u: f32, bary_v: f32) -> [TexCoord; 2] {
+        let first = self.tex_coord(self.uvs, bary_u, bary_v);
+        let second = self
+            .second_uvs
+            .as_deref()
+            .map_or(first, |&uvs| self.tex_coord(Some(uvs), bary_u, bary_v));
+        [first, second]
+    }
+
+    // Interpolated uv, with the gradients of u and v in the triangle's plane from the dual
+    // basis of its edges
+    fn tex_coord(&self, uvs: Option<(Vec3, Vec3, Vec3)>, bary_u: f32, bary_v: f32) -> TexCoord {
+        let denom = self.face_normal.length_squared();
+        let Some((uv0, uv1, uv2)) = uvs else {
+            return TexCoord {
+                u: 0.0,
+                v: 0.0,
+                gradients: (Point::zero(), Point::zero()),
+            };
+        };
+        let w = 1.0 - bary_u - bary_v;
+        let uv = uv0 * w + uv1 * bary_u + uv2 * bary_v;
+        if denom < 1e-20 {
+            return TexCoord {
+                u: uv.x,
+                v: uv.y,
+                gradients: (Point::zero(), Point::zero()),
+            };
+        }
+
+        let dual_ab = Vec3::cross(&self.edge_ac, &self.face_normal) / denom;
+        let dual_ac = Vec3::cross(&self.face_normal, &self.edge_ab) / denom;
+        let duv1 = uv1 - uv0;
+        let duv2 = uv2 - uv0;
+        TexCoord {
+            u: uv.x,
+            v: uv.y,
+            gradients: (
+                dual_ab * duv1.x + dual_ac * duv2.x,
+                dual_ab * duv1.y + dual_ac * duv2.y,
+            ),
+        }
+    }
+
     fn recompute_derived(&mut self) {
         self.edge_ab = self.v1 - self.v0;
         self.edge_ac = self.v2 - self.v0;
@@ -118,12 +231,12 @@ impl Hittable for Tri {
                 let normal = n0 * w + n1 * bary_u + n2 * bary_v;
                 normal.normalize()
             }
-            None => self.face_normal.normalize(),
+            None => self.unit_face_normal(),
         };
 
         let mut normal =
             if !interpolated_normal.is_finite() || interpolated_normal.length_squared() < 1e-6 {
-                self.face_normal.normalize()
+                self.unit_face_normal()
             } else {
                 interpolated_normal
             };
@@ -133,13 +246,6 @@ impl Hittable for Tri {
         }
 
         let point = r.at(dst);
-        let (u, v) = if let Some((uv0, uv1, uv2)) = self.uvs {
-            let w = 1.0 - bary_u - bary_v;
-            let uv = uv0 * w + uv1 * bary_u + uv2 * bary_v;
-            (uv.x, uv.y)
-        } else {
-            (0.0, 0.0)
-        };
 
         let tangent = if let Some(tangents) = self.tangents {
             let t0 = tangents[0];
@@ -165,10 +271,11 @@ impl Hittable for Tri {
             tangent,
             t: dst,
             point,
-            u,
-            v,
             material_index: self.material_index,
+            tex_coords: self.tex_coords(bary_u, bary_v),
             front_face: is_frontface,
+            object_id: 0,
+            footprint: (Point::zero(), Point::zero()),
         })
     }
 
@@ -16,6 +16,46 @@ pub struct GltfData {
     pub buffer_views: Vec<BufferView>,
     pub samplers: Vec<Sampler>,
     pub buffers: Vec<Buffer>,
+    #[serde(default)]
+    pub extensions: GltfExtensions,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct GltfExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    pub lights_punctual: Option<LightsPunctual>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LightsPunctual {
+    pub lights: Vec<Light>,
+}
+
+// Lights shine down -Z of the node they are attached to
+#[derive(Deserialize, Debug)]
+pub struct Light {
+    pub name: Option<String>,
+    pub color: Option<[f64; 3]>,
+    pub intensity: Option<f64>, // Candela for point and spot lights, lux for directional ones
+    pub range: Option<f64>,
+    #[serde(rename = "type")]
+    pub r#type: LightKind,
+    pub spot: Option<LightSpot>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LightKind {
+    Point,
+    Spot,
+    Directional,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LightSpot {
+    pub inner_cone_angle: Option<f64>,
+    pub outer_cone_angle: Option<f64>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -137,6 +177,24 @@ pub struct MaterialExtensions {
     pub transmission: Option<MaterialsTransmission>,
     #[serde(rename = "KHR_materials_ior")]
     pub ior: Option<MaterialsIor>,
+    #[serde(rename = "KHR_materials_emissive_strength")]
+    pub emissive_strength: Option<MaterialsEmissiveStrength>,
+    #[serde(rename = "KHR_materials_clearcoat")]
+    pub clearcoat: Option<MaterialsClearcoat>,
+    #[serde(rename = "KHR_materials_sheen")]
+    pub sheen: Option<MaterialsSheen>,
+    #[serde(rename = "KHR_materials_specular")]
+    pub specular: Option<MaterialsSpecular>,
+    #[serde(rename = "KHR_materials_iridescence")]
+    pub iridescence: Option<MaterialsIridescence>,
+    #[serde(rename = "KHR_materials_anisotropy")]
+    pub anisotropy: Option<MaterialsAnisotropy>,
+    #[serde(rename = "KHR_materials_volume")]
+    pub volume: Option<MaterialsVolume>,
+    #[serde(rename = "KHR_materials_diffuse_transmission")]
+    pub diffuse_transmission: Option<MaterialsDiffuseTransmission>,
+    #[serde(rename = "KHR_materials_volume_scatter")]
+    pub volume_scatter: Option<MaterialsVolumeScatter>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -150,6 +208,85 @@ pub struct MaterialsIor {
     pub ior: f64,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterialsEmissiveStrength {
+    pub emissive_strength: f64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterialsClearcoat {
+    pub clearcoat_factor: Option<f64>,
+    pub clearcoat_texture: Option<Texture>,
+    pub clearcoat_roughness_factor: Option<f64>,
+    pub clearcoat_roughness_texture: Option<Texture>,
+    pub clearcoat_normal_texture: Option<Texture>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterialsSheen {
+    pub sheen_color_factor: Option<[f64; 3]>,
+    pub sheen_color_texture: Option<Texture>,
+    pub sheen_roughness_factor: Option<f64>,
+    pub sheen_roughness_texture: Option<Texture>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterialsSpecular {
+    pub specular_factor: Option<f64>,
+    pub specular_texture: Option<Texture>,
+    pub specular_color_factor: Option<[f64; 3]>,
+    pub specular_color_texture: Option<Texture>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterialsIridescence {
+    pub iridescence_factor: Option<f64>,
+    pub iridescence_texture: Option<Texture>,
+    pub iridescence_ior: Option<f64>,
+    pub iridescence_thickness_minimum: Option<f64>,
+    pub iridescence_thickness_maximum: Option<f64>,
+    pub iridescence_thickness_texture: Option<Texture>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterialsAnisotropy {
+    pub anisotropy_strength: Option<f64>,
+    pub anisotropy_rotation: Option<f64>,
+    pub anisotropy_texture: Option<Texture>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterialsVolume {
+    pub thickness_factor: Option<f64>,
+    pub thickness_texture: Option<Texture>,
+    pub attenuation_distance: Option<f64>,
+    pub attenuation_color: Option<[f64; 3]>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterialsDiffuseTransmission {
+    pub diffuse_transmission_factor: Option<f64>,
+    pub diffuse_transmission_texture: Option<Texture>,
+    pub diffuse_transmission_color_factor: Option<[f64; 3]>,
+    pub diffuse_transmission_color_texture: Option<Texture>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterialsVolumeScatter {
+    pub multiscatter_color: Option<[f64; 3]>,
+    pub multiscatter_color_texture: Option<Texture>,
+    pub scatter_anisotropy: Option<f64>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Material {
@@ -157,20 +294,41 @@ pub struct Material {
     pub double_sided: Option<bool>,
     pub normal_texture: Option<Texture>,
     pub pbr_metallic_roughness: Option<PbrMetallicRoughness>,
+    pub emissive_factor: Option<[f64; 3]>,
+    pub emissive_texture: Option<Texture>,
     #[serde(default)]
     pub extensions: MaterialExtensions,
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct Texture {
     pub index: usize,
+    pub tex_coord: Option<usize>,
+    #[serde(default)]
+    pub extensions: TextureExtensions,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct TextureExtensions {
+    #[serde(rename = "KHR_texture_transform")]
+    pub texture_transform: Option<TextureTransform>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextureTransform {
+    pub offset: Option<[f64; 2]>,
+    pub rotation: Option<f64>, // Counter-clockwise, in radians
+    pub scale: Option<[f64; 2]>,
+    pub tex_coord: Option<usize>, // Overrides the one of the texture
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct PbrMetallicRoughness {
     pub base_color_texture: Option<Texture>,
-    metallic_factor: Option<f64>,
+    pub metallic_factor: Option<f64>,
     pub metallic_roughness_texture: Option<Texture>,
     pub base_color_factor: Option<Vec<f64>>,
     pub roughness_factor: Option<f64>,
@@ -196,6 +354,7 @@ pub struct Attributes {
     pub normal: usize,
     pub tangent: Option<usize>,
     pub texcoord_0: usize,
+    pub texcoord_1: Option<usize>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -207,13 +366,28 @@ pub struct Node {
     pub translation: Option<Vec<f64>>,
     pub children: Option<Vec<usize>>,
     pub matrix: Option<[f64; 16]>,
+    #[serde(default)]
+    pub extensions: NodeExtensions,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct NodeExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    pub light: Option<NodeLight>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct NodeLight {
+    pub light: usize,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Sampler {
-    mag_filter: i64,
-    min_filter: i64,
+    pub mag_filter: Option<i64>,
+    pub min_filter: Option<i64>,
+    pub wrap_s: Option<i64>,
+    pub wrap_t: Option<i64>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -224,7 +398,7 @@ pub struct Scene {
 
 #[derive(Deserialize, Debug)]
 pub struct TextureElement {
-    pub sampler: usize,
+    pub sampler: Option<usize>,
     pub source: usize, // image index
 }
 
@@ -3,6 +3,8 @@ mod gltf;
 
 pub use accessor::AccessorData;
 pub use gltf::{
-    GltfData, Material, Mesh as GltfMesh, MimeType, Node, PbrMetallicRoughness, Primitive,
-    Texture as GltfTexture,
+    GltfData, Light as GltfLight, LightKind, Material, MaterialExtensions, MaterialsAnisotropy,
+    MaterialsClearcoat, MaterialsDiffuseTransmission, MaterialsIridescence, MaterialsSheen,
+    MaterialsSpecular, MaterialsVolume, MaterialsVolumeScatter, Mesh as GltfMesh, MimeType, Node,
+    PbrMetallicRoughness, Primitive, Sampler, Texture as GltfTexture, TextureTransform,
 };
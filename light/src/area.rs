@@ -0,0 +1,81 @@
+use geometry::{AABB, HittableType};
+use util::HitResult;
+
+// Every emissive primitive in the scene. Emitters are picked proportionally to their power and
+// sampled uniformly over their area
+#[derive(Debug)]
+pub struct AreaLight {
+    emitters: Vec<HittableType>,
+    cdf: Vec<f32>,
+    total_power: f32,
+}
+
+impl AreaLight {
+    // luminance gives the average emitted luminance of a material index, returns None if
+    // nothing in the scene emits light
+    pub fn new<F: Fn(usize) -> f32>(objects: &AABB, luminance: F) -> Option<Self> {
+        let mut emitters = vec![];
+        objects.collect_emitters(&|index| luminance(index) > 0.0, &mut emitters);
+
+        let mut total_power = 0.0;
+        let cdf = emitters
+            .iter()
+            .map(|emitter| {
+                total_power += area(emitter) * emitter_luminance(emitter, &luminance);
+                total_power
+            })
+            .collect();
+
+        if total_power <= 0.0 {
+            return None;
+        }
+
+        println!(
+            "Found {} emissive primitives with a total power of {total_power}",
+            emitters.len()
+        );
+        Some(Self {
+            emitters,
+            cdf,
+            total_power,
+        })
+    }
+
+    // Picks an emitter with u_select and a point on it with u
+    pub fn sample(&self, u_select: f32, u: (f32, f32)) -> HitResult {
+        let target = u_select * self.total_power;
+        let index = self
+            .cdf
+            .partition_point(|&power| power <= target)
+            .min(self.emitters.len() - 1);
+
+        match &self.emitters[index] {
+            HittableType::Tri(tri) => tri.sample(u),
+            HittableType::Sphere(sphere) => sphere.sample(u),
+            _ => unreachable!("Emitters are flattened into triangles and spheres"),
+        }
+    }
+
+    // Area density of sample() picking a point on an emitter with the given average luminance.
+    // Power over area leaves only the luminance, so it's the same all over that emitter
+    pub fn pdf_area(&self, luminance: f32) -> f32 {
+        luminance / self.total_power
+    }
+}
+
+fn area(emitter: &HittableType) -> f32 {
+    match emitter {
+        HittableType::Tri(tri) => tri.area(),
+        HittableType::Sphere(sphere) => sphere.area(),
+        _ => 0.0,
+    }
+}
+
+fn emitter_luminance<F: Fn(usize) -> f32>(emitter: &HittableType, luminance: &F) -> f32 {
+    let material_index = match emitter {
+        HittableType::Tri(tri) => tri.material_index(),
+        HittableType::Sphere(sphere) => sphere.material_index,
+        _ => None,
+    };
+    material_index.map_or(0.0, luminance)
+}
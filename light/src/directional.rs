@@ -0,0 +1,25 @@
+use util::{Color, Normalized, Point, Vec3};
+
+use crate::light_trait::{Light, LightSample, transform_direction};
+
+// Infinitely far away light like the sun
+#[derive(Debug)]
+pub struct DirectionalLight {
+    pub direction: Vec3<Normalized>, // Direction the light travels in
+    pub color: Color,
+    pub intensity: f32, // Irradiance on a surface facing the light
+}
+
+impl Light for DirectionalLight {
+    fn sample_li(&self, _point: &Point) -> Option<LightSample> {
+        Some(LightSample {
+            wi: -self.direction,
+            radiance: self.color * self.intensity,
+            distance: f32::INFINITY,
+        })
+    }
+
+    fn transform(&mut self, object_to_world: [[f64; 4]; 4]) {
+        self.direction = transform_direction(object_to_world, &self.direction);
+    }
+}
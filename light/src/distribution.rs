@@ -0,0 +1,67 @@
+#![allow(clippy::cast_precision_loss)]
+
+// Piecewise constant density over [0, 1) with one bucket per value of func
+#[derive(Debug)]
+pub(crate) struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    pub(crate) fn new(func: Vec<f32>) -> Self {
+        let n = func.len() as f32;
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for value in &func {
+            cdf.push(cdf.last().unwrap() + value / n);
+        }
+
+        let integral = *cdf.last().unwrap();
+        // An all black function is sampled uniformly so it never divides by zero
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f32 / n
+            };
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    // Returns the sampled position in [0, 1) and the bucket it falls in
+    pub(crate) fn sample(&self, u: f32) -> (f32, usize) {
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.func.len() - 1);
+
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+
+        ((index as f32 + offset) / self.func.len() as f32, index)
+    }
+
+    // Density of sample() landing anywhere in the bucket
+    pub(crate) fn pdf(&self, index: usize) -> f32 {
+        if self.integral > 0.0 {
+            self.func[index] / self.integral
+        } else {
+            1.0
+        }
+    }
+
+    pub(crate) fn integral(&self) -> f32 {
+        self.integral
+    }
+}
@@ -0,0 +1,153 @@
+#![allow(clippy::cast_precision_loss, clippy::cast_sign_loss)]
+
+use std::{
+    f32::consts::{PI, TAU},
+    path::Path,
+};
+
+use util::{Color, Normalized, Vec3};
+
+use crate::{distribution::Distribution1D, light_trait::LightSample};
+
+// Equirectangular image surrounding the scene, with +Y up and the center of the image along -Z
+#[derive(Debug)]
+pub struct EnvironmentLight {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    rotation: f32, // Around +Y, in radians
+    intensity: f32,
+    rows: Vec<Distribution1D>, // Picks a pixel within each row
+    marginal: Distribution1D,  // Picks a row
+}
+
+impl EnvironmentLight {
+    // Radiance .hdr and OpenEXR files keep their linear values
+    pub fn load(path: &Path, rotation: f32, intensity: f32) -> Self {
+        let image = image::open(path)
+            .unwrap_or_else(|e| panic!("Failed to load environment map {}: {e}", path.display()))
+            .into_rgb32f();
+
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let pixels = image
+            .pixels()
+            .map(|p| Color::new(p[0], p[1], p[2]))
+            .collect();
+
+        println!("Loaded {width}x{height} environment map {}", path.display());
+        Self::new(width, height, pixels, rotation, intensity)
+    }
+
+    // Fills the map by evaluating radiance at the center of every pixel
+    pub fn from_fn<F: Fn(&Vec3<Normalized>) -> Color>(
+        width: usize,
+        height: usize,
+        intensity: f32,
+        radiance: F,
+    ) -> Self {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let u = (x as f32 + 0.5) / width as f32;
+                let v = (y as f32 + 0.5) / height as f32;
+                radiance(&uv_to_direction(u, v, 0.0))
+            })
+            .collect();
+
+        Self::new(width, height, pixels, 0.0, intensity)
+    }
+
+    pub fn new(
+        width: usize,
+        height: usize,
+        pixels: Vec<Color>,
+        rotation: f32,
+        intensity: f32,
+    ) -> Self {
+        // Rows near the poles cover less solid angle, so they are sampled less
+        let rows: Vec<_> = pixels
+            .chunks(width)
+            .enumerate()
+            .map(|(y, row)| {
+                let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+                Distribution1D::new(row.iter().map(|c| c.luminance() * sin_theta).collect())
+            })
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(Distribution1D::integral).collect());
+
+        Self {
+            width,
+            height,
+            pixels,
+            rotation,
+            intensity,
+            rows,
+            marginal,
+        }
+    }
+
+    // Radiance arriving from infinitely far away along -dir
+    pub fn radiance(&self, dir: &Vec3<Normalized>) -> Color {
+        let (u, v) = self.direction_to_uv(dir);
+        let (x, y) = self.pixel(u, v);
+        self.pixels[y * self.width + x] * self.intensity
+    }
+
+    // Picks a direction proportionally to the brightness of the map, returning its solid angle density
+    pub fn sample(&self, u: (f32, f32)) -> Option<(LightSample, f32)> {
+        let (v, y) = self.marginal.sample(u.1);
+        let (u, x) = self.rows[y].sample(u.0);
+
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return None;
+        }
+
+        let pdf = self.marginal.pdf(y) * self.rows[y].pdf(x) / (2.0 * PI * PI * sin_theta);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        let sample = LightSample {
+            wi: uv_to_direction(u, v, self.rotation),
+            radiance: self.pixels[y * self.width + x] * self.intensity,
+            distance: f32::INFINITY,
+        };
+        Some((sample, pdf))
+    }
+
+    // Solid angle density of sample() returning dir
+    pub fn pdf(&self, dir: &Vec3<Normalized>) -> f32 {
+        let (u, v) = self.direction_to_uv(dir);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        let (x, y) = self.pixel(u, v);
+        self.marginal.pdf(y) * self.rows[y].pdf(x) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn direction_to_uv(&self, dir: &Vec3<Normalized>) -> (f32, f32) {
+        let phi = dir.x.atan2(-dir.z) - self.rotation;
+        let u = (phi / TAU + 0.5).rem_euclid(1.0);
+        let v = dir.y.clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    fn pixel(&self, u: f32, v: f32) -> (usize, usize) {
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+        (x, y)
+    }
+}
+
+fn uv_to_direction(u: f32, v: f32, rotation: f32) -> Vec3<Normalized> {
+    let phi = (u - 0.5) * TAU + rotation;
+    let theta = v * PI;
+    Vec3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
@@ -0,0 +1,18 @@
+mod area;
+mod directional;
+mod distribution;
+mod environment;
+mod light_trait;
+mod point;
+mod sky;
+mod spot;
+mod sun;
+
+pub use area::AreaLight;
+pub use directional::DirectionalLight;
+pub use environment::EnvironmentLight;
+pub use light_trait::{Light, LightSample, LightType};
+pub use point::PointLight;
+pub use sky::{PreethamSky, sun_direction};
+pub use spot::SpotLight;
+pub use sun::SunLight;
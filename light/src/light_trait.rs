@@ -0,0 +1,57 @@
+use geometry::mat4_transform_dir;
+use util::{Color, Normalized, Point, Vec3};
+
+use crate::{directional::DirectionalLight, point::PointLight, spot::SpotLight};
+
+pub struct LightSample {
+    pub wi: Vec3<Normalized>, // Direction from the shading point towards the light
+    pub radiance: Color,      // Incident radiance, already divided by the distance squared
+    pub distance: f32,        // Infinite for lights without a position
+}
+
+// Lights with a single direction towards any point, which can't be hit by rays
+pub trait Light {
+    fn sample_li(&self, point: &Point) -> Option<LightSample>;
+    fn transform(&mut self, object_to_world: [[f64; 4]; 4]);
+}
+
+#[derive(Debug)]
+pub enum LightType {
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+}
+
+impl Light for LightType {
+    fn sample_li(&self, point: &Point) -> Option<LightSample> {
+        match self {
+            LightType::Point(light) => light.sample_li(point),
+            LightType::Spot(light) => light.sample_li(point),
+            LightType::Directional(light) => light.sample_li(point),
+        }
+    }
+
+    fn transform(&mut self, object_to_world: [[f64; 4]; 4]) {
+        match self {
+            LightType::Point(light) => light.transform(object_to_world),
+            LightType::Spot(light) => light.transform(object_to_world),
+            LightType::Directional(light) => light.transform(object_to_world),
+        }
+    }
+}
+
+// Scaling only changes where the light points, not how bright it is
+pub(crate) fn transform_direction(
+    object_to_world: [[f64; 4]; 4],
+    direction: &Vec3<Normalized>,
+) -> Vec3<Normalized> {
+    mat4_transform_dir(object_to_world, direction).normalize()
+}
+
+// Smooth window from the KHR_lights_punctual spec so lights with a range reach zero at it
+pub(crate) fn range_falloff(distance_squared: f32, range: Option<f32>) -> f32 {
+    range.map_or(1.0, |range| {
+        let ratio = distance_squared / (range * range);
+        (1.0 - ratio * ratio).clamp(0.0, 1.0).powi(2)
+    })
+}
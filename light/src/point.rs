@@ -0,0 +1,33 @@
+use geometry::mat4_transform_point;
+use util::{Color, Point};
+
+use crate::light_trait::{Light, LightSample, range_falloff};
+
+#[derive(Debug)]
+pub struct PointLight {
+    pub position: Point,
+    pub color: Color,
+    pub intensity: f32,     // Radiant intensity in every direction
+    pub range: Option<f32>, // No light reaches past the range
+}
+
+impl Light for PointLight {
+    fn sample_li(&self, point: &Point) -> Option<LightSample> {
+        let to_light = self.position - *point;
+        let distance_squared = to_light.length_squared();
+        let falloff = range_falloff(distance_squared, self.range);
+        if distance_squared == 0.0 || falloff == 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            wi: to_light.normalize(),
+            radiance: self.color * (self.intensity * falloff / distance_squared),
+            distance: distance_squared.sqrt(),
+        })
+    }
+
+    fn transform(&mut self, object_to_world: [[f64; 4]; 4]) {
+        self.position = mat4_transform_point(object_to_world, self.position);
+    }
+}
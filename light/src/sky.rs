@@ -0,0 +1,161 @@
+#![allow(clippy::unreadable_literal, clippy::many_single_char_names)]
+
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use util::{Color, Normalized, Vec3};
+
+use crate::{environment::EnvironmentLight, sun::SunLight};
+
+// Brings luminance in kcd/m² down to values around 1 for a clear midday sky
+const LUMINANCE_SCALE: f32 = 0.04;
+// Illuminance of the sun above the atmosphere, in klx
+const SUN_ILLUMINANCE: f32 = 128.0;
+// Angular diameter of the sun seen from earth
+const SUN_DIAMETER: f32 = 0.53 * PI / 180.0;
+const SKY_WIDTH: usize = 512;
+const SKY_HEIGHT: usize = 256;
+
+// Clear sky model from "A Practical Analytic Model for Daylight" by Preetham, Shirley and Smits
+#[derive(Debug)]
+pub struct PreethamSky {
+    sun_direction: Vec3<Normalized>,
+    turbidity: f32,
+    zenith: [f32; 3], // Luminance and chromaticity x, y straight up
+    perez: [[f32; 5]; 3],
+}
+
+impl PreethamSky {
+    // Turbidity goes from about 2 for a very clear sky to 10 for a hazy one
+    pub fn new(sun_direction: Vec3<Normalized>, turbidity: f32) -> Self {
+        // The model is only defined for the sun above the horizon
+        let theta_sun = sun_direction.y.clamp(0.0, 1.0).acos();
+        let t = turbidity;
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let thetas = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+        let chromaticity = |m: [[f32; 4]; 3]| {
+            let row = |r: [f32; 4]| r.iter().zip(thetas).map(|(a, b)| a * b).sum::<f32>();
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        Self {
+            sun_direction,
+            turbidity,
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+            perez,
+        }
+    }
+
+    // Directions below the horizon get the color of the horizon
+    pub fn radiance(&self, dir: &Vec3<Normalized>) -> Color {
+        let theta = dir.y.clamp(0.0, 1.0).acos().min(FRAC_PI_2 - 1e-3);
+        let gamma = dir.dot(&self.sun_direction).clamp(-1.0, 1.0).acos();
+        let theta_sun = self.sun_direction.y.clamp(0.0, 1.0).acos();
+
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * perez(self.perez[i], theta, gamma)
+                / perez(self.perez[i], 0.0, theta_sun)
+        });
+
+        xyy_to_rgb(x, y, luminance * LUMINANCE_SCALE)
+    }
+
+    // Sunlight dimmed and reddened by the atmosphere it passes through, None below the horizon
+    pub fn sun(&self, intensity: f32) -> Option<SunLight> {
+        if self.sun_direction.y <= 0.0 {
+            return None;
+        }
+
+        let theta_sun = self.sun_direction.y.acos();
+        // Relative optical mass of the air, accounting for the curvature of the earth
+        let air_mass =
+            1.0 / (theta_sun.cos() + 0.15 * (93.885 - theta_sun.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        // Rayleigh and aerosol extinction at red, green and blue wavelengths in micrometers
+        let transmittance = [0.65, 0.57, 0.475].map(|lambda: f32| {
+            let rayleigh = 0.008735 * lambda.powf(-4.08);
+            let aerosol = beta * lambda.powf(-1.3);
+            (-(rayleigh + aerosol) * air_mass).exp()
+        });
+
+        let angular_radius = SUN_DIAMETER / 2.0;
+        let solid_angle = 2.0 * PI * (1.0 - angular_radius.cos());
+        let radiance = Color::new(transmittance[0], transmittance[1], transmittance[2])
+            * (SUN_ILLUMINANCE * LUMINANCE_SCALE * intensity / solid_angle);
+
+        Some(SunLight::new(self.sun_direction, radiance, angular_radius))
+    }
+
+    // Bakes the sky into an environment map so it can be importance sampled
+    pub fn environment(&self, intensity: f32) -> EnvironmentLight {
+        EnvironmentLight::from_fn(SKY_WIDTH, SKY_HEIGHT, intensity, |dir| self.radiance(dir))
+    }
+}
+
+// Sun direction from degrees above the horizon and degrees clockwise from -Z towards +X
+pub fn sun_direction(elevation: f32, azimuth: f32) -> Vec3<Normalized> {
+    let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+    Vec3::new(
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+        -elevation.cos() * azimuth.cos(),
+    )
+}
+
+// Perez et al. sky luminance distribution
+fn perez([a, b, c, d, e]: [f32; 5], theta: f32, gamma: f32) -> f32 {
+    (1.0 + a * (b / theta.cos().max(1e-3)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos())
+}
+
+// CIE xyY to linear sRGB
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Color {
+    if y <= 0.0 {
+        return Color::zero();
+    }
+
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Color::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+    )
+}
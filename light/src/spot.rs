@@ -0,0 +1,79 @@
+use geometry::mat4_transform_point;
+use util::{Color, Normalized, Point, Vec3};
+
+use crate::light_trait::{Light, LightSample, range_falloff, transform_direction};
+
+#[derive(Debug)]
+pub struct SpotLight {
+    position: Point,
+    direction: Vec3<Normalized>, // Where the spot points
+    color: Color,
+    intensity: f32, // Radiant intensity along the axis
+    cos_inner: f32, // Full intensity inside the inner cone
+    cos_outer: f32, // No light outside the outer cone
+    range: Option<f32>,
+}
+
+impl SpotLight {
+    // Cone angles are measured from the axis, in radians
+    pub fn new(
+        position: Point,
+        direction: Vec3<Normalized>,
+        color: Color,
+        intensity: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+        range: Option<f32>,
+    ) -> Self {
+        Self {
+            position,
+            direction,
+            color,
+            intensity,
+            cos_inner: inner_angle.min(outer_angle).cos(),
+            cos_outer: outer_angle.cos(),
+            range,
+        }
+    }
+
+    // Smoothly fades out between the inner and outer cones
+    fn falloff(&self, cos_theta: f32) -> f32 {
+        if self.cos_inner <= self.cos_outer {
+            return if cos_theta >= self.cos_outer {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        let t = ((cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_li(&self, point: &Point) -> Option<LightSample> {
+        let to_light = self.position - *point;
+        let distance_squared = to_light.length_squared();
+        if distance_squared == 0.0 {
+            return None;
+        }
+
+        let wi = to_light.normalize();
+        let falloff =
+            self.falloff(-wi.dot(&self.direction)) * range_falloff(distance_squared, self.range);
+        if falloff == 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            wi,
+            radiance: self.color * (self.intensity * falloff / distance_squared),
+            distance: distance_squared.sqrt(),
+        })
+    }
+
+    fn transform(&mut self, object_to_world: [[f64; 4]; 4]) {
+        self.position = mat4_transform_point(object_to_world, self.position);
+        self.direction = transform_direction(object_to_world, &self.direction);
+    }
+}
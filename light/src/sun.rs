@@ -0,0 +1,74 @@
+use std::f32::consts::TAU;
+
+use util::{Color, Normalized, Vec3};
+
+use crate::light_trait::LightSample;
+
+// Disk of constant radiance seen from everywhere at the same angle. Unlike DirectionalLight it
+// covers a small solid angle, so rays can hit it and shadows get soft edges
+#[derive(Debug)]
+pub struct SunLight {
+    direction: Vec3<Normalized>, // Towards the sun
+    radiance: Color,
+    cos_max: f32, // Cosine of the angular radius
+}
+
+impl SunLight {
+    pub fn new(direction: Vec3<Normalized>, radiance: Color, angular_radius: f32) -> Self {
+        Self {
+            direction,
+            radiance,
+            cos_max: angular_radius.cos(),
+        }
+    }
+
+    pub fn radiance(&self, dir: &Vec3<Normalized>) -> Color {
+        if dir.dot(&self.direction) >= self.cos_max {
+            self.radiance
+        } else {
+            Color::zero()
+        }
+    }
+
+    // Uniform direction within the disk, with its solid angle density
+    pub fn sample(&self, u: (f32, f32)) -> (LightSample, f32) {
+        let cos_theta = 1.0 - u.0 * (1.0 - self.cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = TAU * u.1;
+
+        let up = if self.direction.y.abs() < 0.9 {
+            Vec3::<Normalized>::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let x = Vec3::cross(&up, &self.direction).normalize();
+        let y = Vec3::cross(&self.direction, &x);
+        let wi = (x * (sin_theta * phi.cos())
+            + y * (sin_theta * phi.sin())
+            + self.direction * cos_theta)
+            .normalize();
+
+        let sample = LightSample {
+            wi,
+            radiance: self.radiance,
+            distance: f32::INFINITY,
+        };
+        (sample, self.cone_pdf())
+    }
+
+    pub fn pdf(&self, dir: &Vec3<Normalized>) -> f32 {
+        if dir.dot(&self.direction) >= self.cos_max {
+            self.cone_pdf()
+        } else {
+            0.0
+        }
+    }
+
+    pub fn solid_angle(&self) -> f32 {
+        TAU * (1.0 - self.cos_max)
+    }
+
+    fn cone_pdf(&self) -> f32 {
+        1.0 / self.solid_angle()
+    }
+}
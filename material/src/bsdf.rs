@@ -0,0 +1,227 @@
+use std::ops::BitOr;
+
+use util::{Color, Normalized, Vec3};
+
+use crate::{conductor::ConductorBxdf, dielectric::DielectricBxdf, lambertian::LambertianBxdf};
+
+// Orthonormal basis with z along the shading normal
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub x: Vec3<Normalized>,
+    pub y: Vec3<Normalized>,
+    pub z: Vec3<Normalized>,
+}
+
+impl Frame {
+    // Branchless basis construction from Duff et al. 2017
+    pub fn from_z(z: Vec3<Normalized>) -> Self {
+        let sign = 1f32.copysign(z.z);
+        let a = -1.0 / (sign + z.z);
+        let b = z.x * z.y * a;
+        Self {
+            x: Vec3::new(1.0 + sign * z.x * z.x * a, sign * b, -sign * z.x),
+            y: Vec3::new(b, sign + z.y * z.y * a, -z.y),
+            z,
+        }
+    }
+
+    // Basis with x as close to the given direction as the normal allows
+    pub fn from_zx(z: Vec3<Normalized>, x: Vec3) -> Self {
+        let x = x - z * z.dot(&x);
+        if x.length_squared() < 1e-8 {
+            return Self::from_z(z);
+        }
+        let x = x.normalize();
+        Self {
+            x,
+            y: Vec3::cross(&z, &x).normalize(),
+            z,
+        }
+    }
+
+    pub fn to_local(&self, v: &Vec3<Normalized>) -> Vec3<Normalized> {
+        Vec3::new(v.dot(&self.x), v.dot(&self.y), v.dot(&self.z))
+    }
+
+    pub fn to_world(&self, v: &Vec3<Normalized>) -> Vec3<Normalized> {
+        (self.x * v.x + self.y * v.y + self.z * v.z).normalize()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LobeFlags(u8);
+
+impl LobeFlags {
+    pub const NONE: Self = Self(0);
+    pub const REFLECTION: Self = Self(1);
+    pub const TRANSMISSION: Self = Self(1 << 1);
+    pub const DIFFUSE: Self = Self(1 << 2);
+    pub const GLOSSY: Self = Self(1 << 3);
+    pub const SPECULAR: Self = Self(1 << 4);
+    pub const PASS: Self = Self(1 << 5); // Straight through without scattering, like alpha cutouts
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // Specular lobes can't be evaluated, only sampled
+    pub fn is_specular(self) -> bool {
+        self.contains(Self::SPECULAR)
+    }
+
+    // Whether eval() can return anything, which light sampling needs
+    pub fn has_non_specular(self) -> bool {
+        self.0 & (Self::DIFFUSE.0 | Self::GLOSSY.0) != 0
+    }
+}
+
+impl BitOr for LobeFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+// For specular samples pdf is the discrete probability of the lobe and f is scaled to match,
+// so f * |cos| / pdf is the path throughput either way
+pub struct BsdfSample {
+    pub wi: Vec3<Normalized>,
+    pub f: Color,
+    pub pdf: f32,
+    pub flags: LobeFlags,
+}
+
+// Directions are in the local shading frame and point away from the surface
+pub trait Bxdf {
+    fn flags(&self) -> LobeFlags;
+    // u_lobe picks a lobe and u a direction within it
+    fn sample(&self, wo: &Vec3<Normalized>, u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample>;
+    fn eval(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color;
+    fn pdf(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32;
+}
+
+#[derive(Debug)]
+pub enum BxdfType {
+    Lambertian(LambertianBxdf),
+    Dielectric(DielectricBxdf),
+    Conductor(ConductorBxdf),
+}
+
+impl Bxdf for BxdfType {
+    fn flags(&self) -> LobeFlags {
+        match self {
+            BxdfType::Lambertian(bxdf) => bxdf.flags(),
+            BxdfType::Dielectric(bxdf) => bxdf.flags(),
+            BxdfType::Conductor(bxdf) => bxdf.flags(),
+        }
+    }
+
+    fn sample(&self, wo: &Vec3<Normalized>, u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        match self {
+            BxdfType::Lambertian(bxdf) => bxdf.sample(wo, u_lobe, u),
+            BxdfType::Dielectric(bxdf) => bxdf.sample(wo, u_lobe, u),
+            BxdfType::Conductor(bxdf) => bxdf.sample(wo, u_lobe, u),
+        }
+    }
+
+    fn eval(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        match self {
+            BxdfType::Lambertian(bxdf) => bxdf.eval(wo, wi),
+            BxdfType::Dielectric(bxdf) => bxdf.eval(wo, wi),
+            BxdfType::Conductor(bxdf) => bxdf.eval(wo, wi),
+        }
+    }
+
+    fn pdf(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        match self {
+            BxdfType::Lambertian(bxdf) => bxdf.pdf(wo, wi),
+            BxdfType::Dielectric(bxdf) => bxdf.pdf(wo, wi),
+            BxdfType::Conductor(bxdf) => bxdf.pdf(wo, wi),
+        }
+    }
+}
+
+// A Bxdf placed at a hit, taking world space directions
+#[derive(Debug)]
+pub struct Bsdf {
+    frame: Frame,
+    geometric_normal: Vec3<Normalized>,
+    bxdf: BxdfType,
+}
+
+impl Bsdf {
+    pub fn new(
+        shading_normal: Vec3<Normalized>,
+        geometric_normal: Vec3<Normalized>,
+        bxdf: BxdfType,
+    ) -> Self {
+        Self::with_frame(Frame::from_z(shading_normal), geometric_normal, bxdf)
+    }
+
+    // For anisotropic lobes, whose frame has x along a tangent direction
+    pub fn with_frame(frame: Frame, geometric_normal: Vec3<Normalized>, bxdf: BxdfType) -> Self {
+        Self {
+            frame,
+            geometric_normal,
+            bxdf,
+        }
+    }
+
+    pub fn flags(&self) -> LobeFlags {
+        self.bxdf.flags()
+    }
+
+    pub fn sample(&self, wo: &Vec3<Normalized>, u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        let wo_local = self.frame.to_local(wo);
+        if wo_local.z == 0.0 {
+            return None;
+        }
+
+        let sample = self.bxdf.sample(&wo_local, u_lobe, u)?;
+        if sample.pdf <= 0.0 || sample.wi.z == 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi: self.frame.to_world(&sample.wi),
+            ..sample
+        })
+    }
+
+    pub fn eval(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        let wi_local = self.frame.to_local(wi);
+        if !self.is_consistent(wi, &wi_local) {
+            return Color::zero();
+        }
+        self.bxdf.eval(&self.frame.to_local(wo), &wi_local)
+    }
+
+    pub fn pdf(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        let wi_local = self.frame.to_local(wi);
+        if !self.is_consistent(wi, &wi_local) {
+            return 0.0;
+        }
+        self.bxdf.pdf(&self.frame.to_local(wo), &wi_local)
+    }
+
+    pub fn abs_cos_theta(&self, wi: &Vec3<Normalized>) -> f32 {
+        wi.dot(&self.frame.z).abs()
+    }
+
+    // Shading normals can put wi on a different side of the shading and geometric surfaces,
+    // which would leak light through the geometry
+    fn is_consistent(&self, wi: &Vec3<Normalized>, wi_local: &Vec3<Normalized>) -> bool {
+        (wi.dot(&self.geometric_normal) > 0.0) == (wi_local.z > 0.0)
+    }
+}
+
+// Cosine-weighted direction in the hemisphere around +z
+pub fn sample_cosine_hemisphere(u: (f32, f32)) -> Vec3<Normalized> {
+    let dir = (Vec3::sample_unit_sphere(u) + Vec3::<Normalized>::new(0.0, 0.0, 1.0)).normalize();
+    if dir.is_finite() {
+        dir
+    } else {
+        Vec3::new(0.0, 0.0, 1.0)
+    }
+}
@@ -0,0 +1,219 @@
+use std::str::FromStr;
+
+use util::{Color, HitResult, Normalized, Ray, Vec3};
+
+use crate::{
+    bsdf::{Bsdf, BsdfSample, Bxdf, BxdfType, LobeFlags},
+    emission::Emission,
+    material_trait::Material,
+    microfacet::{TrowbridgeReitz, reflect},
+};
+
+// Metals with measured complex indices of refraction, averaged over each of the RGB primaries
+#[derive(Clone, Copy, Debug)]
+pub enum ConductorPreset {
+    Gold,
+    Silver,
+    Copper,
+    Aluminium,
+    Chrome,
+}
+
+impl ConductorPreset {
+    // Guesses the metal from a material name like "Gold_Ring" or "chrome-trim"
+    pub fn guess(material_name: &str) -> Option<Self> {
+        let name = material_name.to_lowercase();
+        [
+            ("gold", Self::Gold),
+            ("silver", Self::Silver),
+            ("copper", Self::Copper),
+            ("alumin", Self::Aluminium),
+            ("chrom", Self::Chrome),
+        ]
+        .into_iter()
+        .find_map(|(keyword, preset)| name.contains(keyword).then_some(preset))
+    }
+
+    // Real and imaginary parts of the index of refraction
+    pub fn eta_k(self) -> (Color, Color) {
+        match self {
+            Self::Gold => (
+                Color::new(0.143, 0.374, 1.442),
+                Color::new(3.983, 2.385, 1.603),
+            ),
+            Self::Silver => (
+                Color::new(0.155, 0.117, 0.138),
+                Color::new(4.828, 3.122, 2.147),
+            ),
+            Self::Copper => (
+                Color::new(0.200, 0.924, 1.102),
+                Color::new(3.912, 2.452, 2.142),
+            ),
+            Self::Aluminium => (
+                Color::new(1.657, 0.880, 0.521),
+                Color::new(9.224, 6.270, 4.837),
+            ),
+            Self::Chrome => (
+                Color::new(3.107, 3.181, 2.323),
+                Color::new(3.331, 3.329, 3.135),
+            ),
+        }
+    }
+}
+
+impl FromStr for ConductorPreset {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "gold" => Ok(Self::Gold),
+            "silver" => Ok(Self::Silver),
+            "copper" => Ok(Self::Copper),
+            "aluminium" | "aluminum" => Ok(Self::Aluminium),
+            "chrome" | "chromium" => Ok(Self::Chrome),
+            other => Err(format!("Unknown conductor preset: {other}")),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Conductor {
+    pub name: String,
+    pub eta: Color,
+    pub k: Color,
+    pub roughness: f32,
+    pub emission: Option<Emission>,
+}
+
+impl Conductor {
+    pub fn from_preset(name: String, preset: ConductorPreset, roughness: f32) -> Self {
+        let (eta, k) = preset.eta_k();
+        Self {
+            name,
+            eta,
+            k,
+            roughness,
+            emission: None,
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn bsdf(&self, _ray: &Ray, hit: &HitResult) -> Option<Bsdf> {
+        let bxdf = ConductorBxdf {
+            eta: self.eta,
+            k: self.k,
+            distribution: TrowbridgeReitz::from_roughness(self.roughness),
+        };
+        Some(Bsdf::new(hit.normal, hit.normal, BxdfType::Conductor(bxdf)))
+    }
+
+    fn emitted(&self, hit: &HitResult) -> Color {
+        self.emission
+            .as_ref()
+            .map_or(Color::zero(), |emission| emission.sample(hit))
+    }
+
+    fn average_emission(&self) -> Color {
+        self.emission
+            .as_ref()
+            .map_or(Color::zero(), Emission::average)
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+// GGX reflection with the exact fresnel of a conductor, which tints it
+#[derive(Debug)]
+pub struct ConductorBxdf {
+    eta: Color,
+    k: Color,
+    distribution: TrowbridgeReitz,
+}
+
+impl ConductorBxdf {
+    fn fresnel(&self, cosine: f32) -> Color {
+        Color::new(
+            fresnel_complex(cosine, self.eta.x, self.k.x),
+            fresnel_complex(cosine, self.eta.y, self.k.y),
+            fresnel_complex(cosine, self.eta.z, self.k.z),
+        )
+    }
+}
+
+impl Bxdf for ConductorBxdf {
+    fn flags(&self) -> LobeFlags {
+        if self.distribution.is_smooth() {
+            LobeFlags::REFLECTION | LobeFlags::SPECULAR
+        } else {
+            LobeFlags::REFLECTION | LobeFlags::GLOSSY
+        }
+    }
+
+    fn sample(&self, wo: &Vec3<Normalized>, _u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.is_smooth() {
+            let wi = Vec3::new(-wo.x, -wo.y, wo.z);
+            return Some(BsdfSample {
+                wi,
+                f: self.fresnel(wo.z) / wi.z,
+                pdf: 1.0,
+                flags: LobeFlags::REFLECTION | LobeFlags::SPECULAR,
+            });
+        }
+
+        let wm = self.distribution.sample_wm(wo, u);
+        let wi = reflect(wo, &wm);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            flags: LobeFlags::REFLECTION | LobeFlags::GLOSSY,
+        })
+    }
+
+    fn eval(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 || self.distribution.is_smooth() {
+            return Color::zero();
+        }
+        let wm = (*wo + *wi).normalize();
+        let dg = self.distribution.d(&wm) * self.distribution.g(wo, wi);
+        self.fresnel(wo.dot(&wm)) * (dg / (4.0 * wo.z * wi.z))
+    }
+
+    fn pdf(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        if wo.z <= 0.0 || wi.z <= 0.0 || self.distribution.is_smooth() {
+            return 0.0;
+        }
+        let wm = (*wo + *wi).normalize();
+        self.distribution.visible_d(wo, &wm) / (4.0 * wo.dot(&wm))
+    }
+}
+
+// Unpolarized reflectance of a conductor with index of refraction eta + ik
+fn fresnel_complex(cosine: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = cosine.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cosine.clamp(0.0, 1.0) * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    f32::midpoint(rs, rp)
+}
@@ -1,7 +1,15 @@
-use rand::RngExt;
-use util::{Color, HitResult, Normalized, Ray, THREAD_RNG, Unnormalized, Vec3};
+use std::f32::consts::FRAC_1_PI;
 
-use crate::material_trait::Material;
+use util::{Color, HitResult, Normalized, Ray, Unnormalized, Vec3};
+
+use crate::{
+    bsdf::{Bsdf, BsdfSample, Bxdf, BxdfType, LobeFlags, sample_cosine_hemisphere},
+    emission::Emission,
+    lambertian::Albedo,
+    material_trait::{Interior, Material},
+    microfacet::{TrowbridgeReitz, reflect},
+    texture::Texture,
+};
 
 #[derive(Debug)]
 pub struct Dielectric {
@@ -9,41 +17,65 @@ pub struct Dielectric {
     albedo: Color,
     refraction_index: f32,
     transmission_factor: f32,
+    roughness: Texture, // In the green channel, like glTF's metallic-roughness texture
+    absorption: Option<Color>, // None for thin walls, which tint light each time it passes
+    medium: Option<usize>,
+    priority: u32,
+    emission: Option<Emission>,
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit: &HitResult) -> (Ray, Color) {
-        if random_double() > self.transmission_factor {
-            // Treat as opaque lambertian
-            let mut scatter_dir = hit.normal + Vec3::<Unnormalized>::random_in_unit_sphere();
-            if scatter_dir.dot(&hit.normal) < 0.0 {
-                scatter_dir = -scatter_dir;
-            }
-            let origin = hit.point + hit.normal * (hit.t * 1e-4).max(1e-4);
-            return (Ray::new(origin, scatter_dir.normalize()), self.albedo);
-        }
+    fn bsdf(&self, ray: &Ray, hit: &HitResult) -> Option<Bsdf> {
+        self.nested_bsdf(ray, hit, 1.0)
+    }
 
+    fn nested_bsdf(&self, _ray: &Ray, hit: &HitResult, outside_ior: f32) -> Option<Bsdf> {
         let ri = if hit.front_face {
-            1.0 / self.refraction_index
+            outside_ior / self.refraction_index
         } else {
-            self.refraction_index
+            self.refraction_index / outside_ior
         };
 
-        let unit_dir = ray.dir;
-        let cos_theta = f32::min(-unit_dir.dot(&hit.normal), 1.0);
-        let sin_theta = f32::sqrt(1.0 - cos_theta * cos_theta);
-
-        let cannot_refract = ri * sin_theta > 1.0;
-        let dir = if cannot_refract || reflectance(cos_theta, ri) > random_double() {
-            unit_dir.reflect(&hit.normal)
+        // The volume colors light by the distance it travels inside, so the surface only tints
+        // it once on the way in
+        let albedo = if self.absorption.is_some() && !hit.front_face {
+            Color::new(1.0, 1.0, 1.0)
         } else {
-            Self::refract(unit_dir, hit.normal, ri)
+            self.albedo
         };
 
-        let origin = hit.point + dir * (hit.t * 1e-4).max(1e-4);
-        let new_ray = Ray::new(origin, dir);
+        let bxdf = DielectricBxdf::new(
+            albedo,
+            ri,
+            self.transmission_factor,
+            TrowbridgeReitz::from_roughness(self.roughness.sample(hit).y),
+        );
+        Some(Bsdf::new(
+            hit.normal,
+            hit.normal,
+            BxdfType::Dielectric(bxdf),
+        ))
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        Some(Interior {
+            ior: self.refraction_index,
+            absorption: self.absorption.unwrap_or(Color::zero()),
+            medium: self.medium,
+            priority: self.priority,
+        })
+    }
+
+    fn emitted(&self, hit: &HitResult) -> Color {
+        self.emission
+            .as_ref()
+            .map_or(Color::zero(), |emission| emission.sample(hit))
+    }
 
-        (new_ray, self.albedo)
+    fn average_emission(&self) -> Color {
+        self.emission
+            .as_ref()
+            .map_or(Color::zero(), Emission::average)
     }
 
     fn get_name(&self) -> &str {
@@ -57,20 +89,285 @@ impl Dielectric {
         albedo: Option<Color>,
         refraction_index: f32,
         transmission_factor: f32,
+        roughness: Texture,
+        absorption: Option<Color>,
+        emission: Option<Emission>,
     ) -> Self {
         Self {
             name,
             albedo: albedo.unwrap_or(Color::new(1.0, 1.0, 1.0)),
             refraction_index,
             transmission_factor,
+            roughness,
+            absorption,
+            medium: None,
+            priority: 0,
+            emission,
+        }
+    }
+
+    pub fn set_priority(&mut self, priority: u32) {
+        self.priority = priority;
+    }
+
+    pub fn set_medium(&mut self, medium: usize) {
+        self.medium = Some(medium);
+    }
+
+    pub fn take_emission(&mut self) -> Option<Emission> {
+        self.emission.take()
+    }
+}
+
+// Glass, smooth or frosted, or an opaque lambertian for the part that doesn't transmit
+#[derive(Debug)]
+pub struct DielectricBxdf {
+    albedo: Color,
+    opaque_albedo: Color,
+    ri: f32, // Ratio of refraction indices across the surface in the direction of travel
+    transmission_factor: f32,
+    distribution: TrowbridgeReitz,
+}
+
+impl DielectricBxdf {
+    pub(crate) fn new(
+        albedo: Color,
+        ri: f32,
+        transmission_factor: f32,
+        distribution: TrowbridgeReitz,
+    ) -> Self {
+        Self {
+            albedo,
+            opaque_albedo: albedo,
+            ri,
+            transmission_factor,
+            distribution,
         }
     }
 
-    fn refract(uv: Vec3<Normalized>, n: Vec3<Normalized>, ri: f32) -> Vec3<Normalized> {
-        let cos_theta = f32::min(-uv.dot(&n), 1.0);
-        let r_out_perp = (uv + n * cos_theta) * ri;
-        let r_out_parallel = n * -f32::sqrt(f32::abs(1.0 - r_out_perp.length_squared()));
-        (r_out_perp + r_out_parallel).normalize()
+    // For surfaces whose opaque part is colored differently from the light they let through
+    #[must_use]
+    pub(crate) fn with_opaque_albedo(self, opaque_albedo: Color) -> Self {
+        Self {
+            opaque_albedo,
+            ..self
+        }
+    }
+}
+
+impl Bxdf for DielectricBxdf {
+    fn flags(&self) -> LobeFlags {
+        let mut flags = LobeFlags::NONE;
+        if self.transmission_factor < 1.0 {
+            flags = flags | LobeFlags::REFLECTION | LobeFlags::DIFFUSE;
+        }
+        if self.transmission_factor > 0.0 {
+            let lobe = if self.distribution.is_smooth() {
+                LobeFlags::SPECULAR
+            } else {
+                LobeFlags::GLOSSY
+            };
+            flags = flags | LobeFlags::REFLECTION | LobeFlags::TRANSMISSION | lobe;
+        }
+        flags
+    }
+
+    fn sample(&self, wo: &Vec3<Normalized>, u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        // The opaque part scatters into the hemisphere the ray came from
+        let side = wo.z.signum();
+        let opaque_prob = 1.0 - self.transmission_factor;
+        if u_lobe < opaque_prob {
+            let wi = sample_cosine_hemisphere(u);
+            let wi = Vec3::new(wi.x, wi.y, wi.z * side);
+            return Some(BsdfSample {
+                wi,
+                f: self.eval(wo, &wi),
+                pdf: self.pdf(wo, &wi),
+                flags: LobeFlags::REFLECTION | LobeFlags::DIFFUSE,
+            });
+        }
+        let u_lobe = (u_lobe - opaque_prob) / self.transmission_factor;
+
+        if !self.distribution.is_smooth() {
+            return self.sample_rough(wo, u_lobe, u);
+        }
+
+        let cos_theta = wo.z.abs().min(1.0);
+        let reflect_prob = fresnel(cos_theta, self.ri);
+
+        let (wi, prob, flags) = if u_lobe < reflect_prob {
+            let wi = Vec3::new(-wo.x, -wo.y, wo.z);
+            (wi, reflect_prob, LobeFlags::REFLECTION)
+        } else {
+            let wi = refract(wo, self.ri, side);
+            (wi, 1.0 - reflect_prob, LobeFlags::TRANSMISSION)
+        };
+
+        let pdf = self.transmission_factor * prob;
+        Some(BsdfSample {
+            wi,
+            f: self.albedo * (pdf / wi.z.abs()),
+            pdf,
+            flags: flags | LobeFlags::SPECULAR,
+        })
+    }
+
+    fn eval(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        let opaque = if wo.z * wi.z > 0.0 {
+            self.opaque_albedo * ((1.0 - self.transmission_factor) * FRAC_1_PI)
+        } else {
+            Color::zero()
+        };
+        if self.distribution.is_smooth() {
+            return opaque;
+        }
+        opaque + self.eval_rough(wo, wi) * self.transmission_factor
+    }
+
+    fn pdf(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        let opaque = if wo.z * wi.z > 0.0 {
+            (1.0 - self.transmission_factor) * wi.z.abs() * FRAC_1_PI
+        } else {
+            0.0
+        };
+        if self.distribution.is_smooth() {
+            return opaque;
+        }
+        opaque + self.pdf_rough(wo, wi) * self.transmission_factor
+    }
+}
+
+// Microfacet reflection and transmission from "Microfacet Models for Refraction through Rough
+// Surfaces" by Walter et al. Directions are flipped so wo is on the +z side, where light arrives
+impl DielectricBxdf {
+    fn sample_rough(
+        &self,
+        wo: &Vec3<Normalized>,
+        u_lobe: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        let flipped = wo.z < 0.0;
+        let wo_up = flip(wo, flipped);
+        let wm = self.distribution.sample_wm(&wo_up, u);
+
+        let (wi, flags) = if u_lobe < fresnel(wo_up.dot(&wm), self.ri) {
+            let wi = reflect(&wo_up, &wm);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            (wi, LobeFlags::REFLECTION)
+        } else {
+            let wi = refract_facet(&wo_up, &wm, self.ri)?;
+            if wi.z >= 0.0 {
+                return None;
+            }
+            (wi, LobeFlags::TRANSMISSION)
+        };
+
+        let wi = flip(&wi, flipped);
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            flags: flags | LobeFlags::GLOSSY,
+        })
+    }
+
+    fn eval_rough(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        let flipped = wo.z < 0.0;
+        let (wo, wi) = (flip(wo, flipped), flip(wi, flipped));
+        let Some((wm, eta)) = self.half_vector(&wo, &wi) else {
+            return Color::zero();
+        };
+
+        let reflectance = fresnel(wo.dot(&wm), self.ri);
+        let dg = self.distribution.d(&wm) * self.distribution.g(&wo, &wi);
+        if wi.z > 0.0 {
+            return self.albedo * (dg * reflectance / (4.0 * wo.z * wi.z));
+        }
+
+        // Radiance isn't scaled by the change in solid angle across the surface, matching the
+        // smooth case, since it cancels out on the way back out of a closed object
+        let denom = (wi.dot(&wm) + wo.dot(&wm) / eta).powi(2);
+        let ratio = (wi.dot(&wm) * wo.dot(&wm) / (wi.z * wo.z * denom)).abs();
+        self.albedo * (dg * (1.0 - reflectance) * ratio)
+    }
+
+    fn pdf_rough(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        let flipped = wo.z < 0.0;
+        let (wo, wi) = (flip(wo, flipped), flip(wi, flipped));
+        let Some((wm, eta)) = self.half_vector(&wo, &wi) else {
+            return 0.0;
+        };
+
+        let reflectance = fresnel(wo.dot(&wm), self.ri);
+        let visible = self.distribution.visible_d(&wo, &wm);
+        if wi.z > 0.0 {
+            return visible / (4.0 * wo.dot(&wm)) * reflectance;
+        }
+
+        let denom = (wi.dot(&wm) + wo.dot(&wm) / eta).powi(2);
+        visible * wi.dot(&wm).abs() / denom * (1.0 - reflectance)
+    }
+
+    // Microfacet normal that scatters wo into wi, with the ratio of refraction indices it
+    // crosses. None when it faces away from either direction
+    fn half_vector(
+        &self,
+        wo: &Vec3<Normalized>,
+        wi: &Vec3<Normalized>,
+    ) -> Option<(Vec3<Normalized>, f32)> {
+        if wo.z == 0.0 || wi.z == 0.0 {
+            return None;
+        }
+        let eta = if wi.z > 0.0 { 1.0 } else { 1.0 / self.ri };
+        let wm = *wi * eta + *wo;
+        if wm.length_squared() < 1e-12 {
+            return None;
+        }
+        let wm = wm.normalize();
+        let wm = if wm.z < 0.0 { -wm } else { wm };
+
+        if wm.dot(wi) * wi.z <= 0.0 || wm.dot(wo) <= 0.0 {
+            return None;
+        }
+        Some((wm, eta))
+    }
+}
+
+// Refracts wo through a surface whose normal is +z on the side of wo
+fn refract(wo: &Vec3<Normalized>, ri: f32, side: f32) -> Vec3<Normalized> {
+    let r_out_perp = Vec3::<Unnormalized>::new(-wo.x, -wo.y, 0.0) * ri;
+    let cos_out = f32::sqrt(f32::abs(1.0 - r_out_perp.length_squared()));
+    Vec3::new(r_out_perp.x, r_out_perp.y, -cos_out * side).normalize()
+}
+
+fn flip(w: &Vec3<Normalized>, flipped: bool) -> Vec3<Normalized> {
+    if flipped { -*w } else { *w }
+}
+
+// Refracts wo through a microfacet on the same side, None past the critical angle
+fn refract_facet(
+    wo: &Vec3<Normalized>,
+    wm: &Vec3<Normalized>,
+    ri: f32,
+) -> Option<Vec3<Normalized>> {
+    let cos_in = wo.dot(wm);
+    let sin2_out = ri * ri * (1.0 - cos_in * cos_in).max(0.0);
+    if sin2_out >= 1.0 {
+        return None;
+    }
+    let cos_out = (1.0 - sin2_out).sqrt();
+    Some((-*wo * ri + *wm * (ri * cos_in - cos_out)).normalize())
+}
+
+// Fraction of light reflected, which is all of it past the critical angle
+fn fresnel(cosine: f32, ri: f32) -> f32 {
+    let sin_theta = (1.0 - cosine * cosine).max(0.0).sqrt();
+    if ri * sin_theta > 1.0 {
+        1.0
+    } else {
+        reflectance(cosine, ri)
     }
 }
 
@@ -80,7 +377,3 @@ fn reflectance(cosine: f32, refraction_index: f32) -> f32 {
 
     r0 + (1.0 - r0) * f32::powf(1.0 - cosine, 5.0)
 }
-
-fn random_double() -> f32 {
-    THREAD_RNG.with(|rng| rng.borrow_mut().random::<f32>())
-}
@@ -0,0 +1,38 @@
+use util::{Color, HitResult};
+
+use crate::{lambertian::Albedo, texture::Texture};
+
+// Light given off by a surface that also scatters, like the glTF emissive properties
+#[derive(Debug)]
+pub struct Emission {
+    color: Color, // Includes the emissive strength
+    texture: Option<Texture>,
+    average: Color,
+}
+
+impl Emission {
+    pub fn new(color: Color, texture: Option<Texture>) -> Self {
+        let average = match &texture {
+            Some(texture) if !texture.texels().is_empty() => color * texture.average(),
+            _ => color,
+        };
+
+        Self {
+            color,
+            texture,
+            average,
+        }
+    }
+
+    pub fn sample(&self, hit: &HitResult) -> Color {
+        match &self.texture {
+            Some(texture) => self.color * texture.sample(hit),
+            None => self.color,
+        }
+    }
+
+    // Mean over the whole texture, which is what lights are picked by
+    pub fn average(&self) -> Color {
+        self.average
+    }
+}
@@ -1,6 +1,6 @@
 use util::{Color, HitResult, Ray};
 
-use crate::material_trait::Material;
+use crate::{bsdf::Bsdf, material_trait::Material};
 
 #[derive(Debug)]
 pub struct Emissive {
@@ -10,11 +10,17 @@ pub struct Emissive {
 }
 
 impl Material for Emissive {
-    fn scatter(&self, ray: &Ray, hit_record: &HitResult) -> (Ray, Color) {
-        (
-            Ray::new(hit_record.point, ray.dir),
-            self.color * self.intensity,
-        )
+    // Emitters absorb everything that reaches them
+    fn bsdf(&self, _ray: &Ray, _hit_record: &HitResult) -> Option<Bsdf> {
+        None
+    }
+
+    fn emitted(&self, _hit_record: &HitResult) -> Color {
+        self.color * self.intensity
+    }
+
+    fn average_emission(&self) -> Color {
+        self.color * self.intensity
     }
 
     fn get_name(&self) -> &str {
@@ -0,0 +1,37 @@
+use util::{Color, HitResult, Ray};
+
+use crate::{
+    bsdf::Bsdf,
+    material_trait::{Interior, Material},
+};
+
+// Invisible boundary of a medium, which paths cross without scattering
+#[derive(Debug)]
+pub struct Interface {
+    pub name: String,
+    pub medium: usize,
+    pub priority: u32,
+}
+
+impl Material for Interface {
+    fn bsdf(&self, _ray: &Ray, _hit_record: &HitResult) -> Option<Bsdf> {
+        None
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        Some(Interior {
+            ior: 1.0,
+            absorption: Color::zero(),
+            medium: Some(self.medium),
+            priority: self.priority,
+        })
+    }
+
+    fn is_interface(&self) -> bool {
+        true
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
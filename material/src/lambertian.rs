@@ -1,8 +1,17 @@
-use rand::RngExt;
+use std::f32::consts::{FRAC_1_PI, PI};
 
-use util::{Color, HitResult, Ray, THREAD_RNG, Unnormalized, Vec3};
+use util::{Color, HitResult, Normalized, Ray, Vec3};
 
-use crate::{material_trait::Material, texture::Texture};
+use crate::{
+    bsdf::{Bsdf, BsdfSample, Bxdf, BxdfType, Frame, LobeFlags, sample_cosine_hemisphere},
+    emission::Emission,
+    layers::Layers,
+    material_trait::Material,
+    microfacet::{TrowbridgeReitz, ior_to_f0, reflect, schlick, specular_albedo},
+    sheen::SheenLobe,
+    texture::Texture,
+    thin_film::ThinFilm,
+};
 
 pub trait Albedo {
     fn sample(&self, hit: &HitResult) -> Color;
@@ -15,14 +24,14 @@ impl Albedo for Color {
 }
 
 impl Albedo for Texture {
-    #[allow(clippy::cast_sign_loss, clippy::cast_precision_loss)]
     fn sample(&self, hit: &HitResult) -> Color {
-        let x = (hit.u * self.width as f32) as usize % self.width;
-        let y = (hit.v * self.height as f32) as usize % self.height;
-        self.data[y * self.width + x]
+        self.sample_hit(hit)
     }
 }
 
+// Index of refraction of the dielectric base unless set otherwise, and of the clearcoat
+const DEFAULT_IOR: f32 = 1.5;
+
 #[derive(Debug)]
 pub struct LambertianBase<TAlbedo, TORM> {
     pub name: String,
@@ -30,107 +39,434 @@ pub struct LambertianBase<TAlbedo, TORM> {
     pub normal_texture: Option<Texture>,
     pub orm: TORM,
     pub alpha: f32,
+    pub emission: Option<Emission>,
+    pub layers: Layers,
 }
 
 impl<TAlbedo: Albedo + Sync + Send, TORM: Albedo + Sync + Send> Material
     for LambertianBase<TAlbedo, TORM>
 {
-    fn scatter(&self, ray: &Ray, hit: &HitResult) -> (Ray, Color) {
-        if self.alpha < 1.0 {
-            let transparency_decision = THREAD_RNG.with(|rng| {
-                let mut rng = rng.borrow_mut();
-                if rng.random::<f32>() < 1f32 - self.alpha {
-                    Some((Ray::new(hit.point, ray.dir), Color::new(1.0, 1.0, 1.0)))
-                } else {
-                    None
-                }
+    fn bsdf(&self, _ray: &Ray, hit: &HitResult) -> Option<Bsdf> {
+        let orm = self.orm.sample(hit);
+        let roughness = orm.y.clamp(0.0, 1.0);
+        let shading_normal = normal_mapped(self.normal_texture.as_ref(), hit);
+        let (frame, distribution) = self.specular_frame(hit, shading_normal, roughness);
+
+        let (specular_weight, specular_color) = self
+            .layers
+            .specular
+            .as_ref()
+            .map_or((1.0, Color::new(1.0, 1.0, 1.0)), |specular| {
+                (specular.factor.sample(hit).x, specular.color.sample(hit))
             });
-            if let Some(result) = transparency_decision {
-                return result;
-            }
+        let f0 = ior_to_f0(self.layers.ior.unwrap_or(DEFAULT_IOR), 1.0);
+        let dielectric_f0 =
+            Color::min(&(specular_color * f0), &Color::new(1.0, 1.0, 1.0)) * specular_weight;
+
+        let bxdf = LambertianBxdf {
+            albedo: self.albedo.sample(hit),
+            roughness,
+            distribution,
+            metallic: orm.z.clamp(0.0, 1.0),
+            alpha: self.alpha.min(1.0),
+            dielectric_f0,
+            specular_weight,
+            iridescence: self
+                .layers
+                .iridescence
+                .as_ref()
+                .map(|iridescence| ThinFilm {
+                    factor: iridescence.factor.sample(hit).x,
+                    ior: iridescence.ior,
+                    thickness: iridescence.thickness.sample(hit).x,
+                }),
+            sheen: self.layers.sheen.as_ref().map(|sheen| {
+                SheenLobe::new(sheen.color.sample(hit), sheen.roughness.sample(hit).x)
+            }),
+            clearcoat: self.layers.clearcoat.as_ref().map(|clearcoat| {
+                let normal = normal_mapped(clearcoat.normal_texture.as_ref(), hit);
+                CoatLobe::new(
+                    clearcoat.factor.sample(hit).x,
+                    clearcoat.roughness.sample(hit).x,
+                    frame.to_local(&normal),
+                )
+            }),
+        };
+
+        Some(Bsdf::with_frame(
+            frame,
+            hit.normal,
+            BxdfType::Lambertian(bxdf),
+        ))
+    }
+
+    fn emitted(&self, hit: &HitResult) -> Color {
+        self.emission
+            .as_ref()
+            .map_or(Color::zero(), |emission| emission.sample(hit))
+    }
+
+    fn average_emission(&self) -> Color {
+        self.emission
+            .as_ref()
+            .map_or(Color::zero(), Emission::average)
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<TAlbedo: Albedo, TORM: Albedo> LambertianBase<TAlbedo, TORM> {
+    // Shading frame with x along the anisotropy direction, and the distribution of the specular
+    // lobe stretched along it
+    fn specular_frame(
+        &self,
+        hit: &HitResult,
+        normal: Vec3<Normalized>,
+        roughness: f32,
+    ) -> (Frame, TrowbridgeReitz) {
+        let Some(anisotropy) = &self.layers.anisotropy else {
+            return (
+                Frame::from_z(normal),
+                TrowbridgeReitz::from_roughness(roughness),
+            );
+        };
+
+        // Without tangents the direction is relative to an arbitrary one
+        let (t, b) = hit.tangent.unwrap_or_else(|| {
+            let frame = Frame::from_z(normal);
+            (frame.x, frame.y)
+        });
+        let texel = anisotropy.direction.sample(hit);
+        let frame = Frame::from_zx(normal, t * texel.x + b * texel.y);
+
+        let alpha = roughness * roughness;
+        let alpha_t = alpha + (1.0 - alpha) * texel.z * texel.z;
+        (frame, TrowbridgeReitz::new(alpha_t, alpha))
+    }
+}
+
+// Sample normal map and transform to world space
+pub(crate) fn normal_mapped(normal_map: Option<&Texture>, hit: &HitResult) -> Vec3<Normalized> {
+    if let Some(normal_map) = normal_map
+        && let Some((t, b)) = hit.tangent
+    {
+        // Sample the normal map (RGB → XYZ in tangent space)
+        let texel = normal_map.sample(hit); // gives [0,1] RGB
+        let tangent_normal = Vec3::new(
+            texel.x * 2.0 - 1.0,
+            texel.y * 2.0 - 1.0,
+            texel.z * 2.0 - 1.0,
+        )
+        .normalize();
+
+        // Transform from tangent space to world space using TBN
+        let n = hit.normal;
+        (t * tangent_normal.x + b * tangent_normal.y + n * tangent_normal.z).normalize()
+    } else {
+        hit.normal
+    }
+}
+
+// glTF metallic-roughness BRDF: a GGX specular lobe over a diffuse base that fades out with
+// metalness, under optional sheen and clearcoat layers, plus an alpha cutout
+#[derive(Debug)]
+pub struct LambertianBxdf {
+    albedo: Color,
+    roughness: f32,
+    distribution: TrowbridgeReitz,
+    metallic: f32,
+    alpha: f32,
+    dielectric_f0: Color, // Tinted and scaled by the specular extension
+    specular_weight: f32, // Dielectric reflectance at grazing angles
+    iridescence: Option<ThinFilm>,
+    sheen: Option<SheenLobe>,
+    clearcoat: Option<CoatLobe>,
+}
+
+impl LambertianBxdf {
+    // Reflectance of the specular lobe, blending the dielectric and metal ones
+    fn fresnel(&self, cosine: f32) -> Color {
+        let mut dielectric = schlick(self.dielectric_f0, self.specular_weight, cosine);
+        let mut metal = schlick(self.albedo, 1.0, cosine);
+        if let Some(film) = &self.iridescence {
+            let lerp = |a: Color, b: Color| a * (1.0 - film.factor) + b * film.factor;
+            let film_dielectric = film.fresnel(cosine, self.dielectric_f0) * self.specular_weight;
+            dielectric = lerp(dielectric, film_dielectric);
+            metal = lerp(metal, film.fresnel(cosine, self.albedo));
         }
+        dielectric * (1.0 - self.metallic) + metal * self.metallic
+    }
 
-        // Sample normal map and transform to world space
-        let shading_normal = if let Some(normal_map) = &self.normal_texture
-            && let Some((t, b)) = hit.tangent
-        {
-            // Sample the normal map (RGB → XYZ in tangent space)
-            let sampled = normal_map.sample(hit); // gives [0,1] RGB
-            let tangent_normal = Vec3::new(
-                sampled.x * 2.0 - 1.0,
-                sampled.y * 2.0 - 1.0,
-                sampled.z * 2.0 - 1.0,
-            )
-            .normalize();
-
-            // Transform from tangent space to world space using TBN
-            let n = hit.normal;
-            (t * tangent_normal.x + b * tangent_normal.y + n * tangent_normal.z).normalize()
+    // Fraction of light the specular lobe reflects towards a direction at the given cosine
+    fn specular_albedo(&self, cosine: f32) -> Color {
+        let dielectric = specular_albedo(
+            self.dielectric_f0,
+            self.specular_weight,
+            self.roughness,
+            cosine,
+        );
+        let metal = specular_albedo(self.albedo, 1.0, self.roughness, cosine);
+        dielectric * (1.0 - self.metallic) + metal * self.metallic
+    }
+
+    // The diffuse base only gets what the dielectric specular layer doesn't reflect towards wo
+    fn diffuse(&self, cosine: f32) -> Color {
+        let reflected = specular_albedo(
+            self.dielectric_f0,
+            self.specular_weight,
+            self.roughness,
+            cosine,
+        );
+        let reflected = reflected.x.max(reflected.y).max(reflected.z);
+        self.albedo * ((1.0 - self.metallic) * (1.0 - reflected) * FRAC_1_PI)
+    }
+
+    // How much light towards wo makes it through the clearcoat, and then through the sheen
+    fn transmitted(&self, wo: &Vec3<Normalized>) -> (f32, f32) {
+        let coat = self.clearcoat.as_ref().map_or(0.0, |coat| coat.albedo(wo));
+        let sheen = self.sheen.as_ref().map_or(0.0, |sheen| sheen.albedo(wo.z));
+        (1.0 - coat, 1.0 - sheen)
+    }
+
+    // Chances of sampling the clearcoat, the specular lobe and the cosine lobe shared by the
+    // diffuse and the sheen, from how much each reflects towards wo
+    fn lobe_probs(&self, wo: &Vec3<Normalized>) -> [f32; 3] {
+        let (under_coat, under_sheen) = self.transmitted(wo);
+        let coat = 1.0 - under_coat;
+        let specular = under_coat * under_sheen * self.specular_albedo(wo.z).luminance();
+        let sheen = 1.0 - under_sheen;
+        let cosine = under_coat * (sheen + under_sheen * (self.diffuse(wo.z) * PI).luminance());
+
+        let total = coat + specular + cosine;
+        if total > 0.0 {
+            [coat / total, specular / total, cosine / total]
         } else {
-            hit.normal
-        };
+            [0.0, 1.0, 0.0]
+        }
+    }
 
-        let orm = self.orm.sample(hit);
-        let roughness = orm.y;
-        let metallic = orm.z;
-        let cos_theta = (-ray.dir.dot(&shading_normal)).max(0.0);
-        let fresnel = schlick(cos_theta, 1.5);
-
-        let reflect_prob = f32::max(fresnel, metallic);
-        let mut scatter_direction =
-            if THREAD_RNG.with(|rng| rng.borrow_mut().random::<f32>()) < reflect_prob {
-                ray.dir.reflect(&shading_normal)
+    fn specular(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        if self.distribution.is_smooth() {
+            return Color::zero();
+        }
+        let wm = (*wo + *wi).normalize();
+        self.fresnel(wo.dot(&wm))
+            * (self.distribution.d(&wm) * self.distribution.g(wo, wi) / (4.0 * wo.z * wi.z))
+    }
+
+    fn specular_pdf(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        if self.distribution.is_smooth() {
+            return 0.0;
+        }
+        let wm = (*wo + *wi).normalize();
+        self.distribution.visible_d(wo, &wm) / (4.0 * wo.dot(&wm).abs())
+    }
+}
+
+impl Bxdf for LambertianBxdf {
+    fn flags(&self) -> LobeFlags {
+        let mut flags = LobeFlags::REFLECTION;
+        if self.metallic < 1.0 || self.sheen.is_some() {
+            flags = flags | LobeFlags::DIFFUSE;
+        }
+        let coat_smooth = self
+            .clearcoat
+            .as_ref()
+            .map(|coat| coat.distribution.is_smooth());
+        for smooth in [Some(self.distribution.is_smooth()), coat_smooth]
+            .into_iter()
+            .flatten()
+        {
+            flags = flags
+                | if smooth {
+                    LobeFlags::SPECULAR
+                } else {
+                    LobeFlags::GLOSSY
+                };
+        }
+        if self.alpha < 1.0 {
+            flags = flags | LobeFlags::TRANSMISSION | LobeFlags::SPECULAR;
+        }
+        flags
+    }
+
+    fn sample(&self, wo: &Vec3<Normalized>, u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        // Alpha cutout lets the ray pass straight through
+        let pass_prob = 1.0 - self.alpha;
+        if u_lobe < pass_prob {
+            return Some(BsdfSample {
+                wi: -*wo,
+                f: Color::new(1.0, 1.0, 1.0) * (pass_prob / wo.z.abs()),
+                pdf: pass_prob,
+                flags: LobeFlags::TRANSMISSION | LobeFlags::SPECULAR | LobeFlags::PASS,
+            });
+        }
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let u_lobe = (u_lobe - pass_prob) / self.alpha;
+        let [coat_prob, specular_prob, _] = self.lobe_probs(wo);
+
+        let (wi, smooth_reflectance) = if u_lobe < coat_prob {
+            let coat = self.clearcoat.as_ref()?;
+            let wi = coat.sample(wo, u);
+            let reflectance = coat
+                .distribution
+                .is_smooth()
+                .then(|| (Color::new(1.0, 1.0, 1.0) * coat.reflectance(wo), coat_prob));
+            (wi, reflectance)
+        } else if u_lobe < coat_prob + specular_prob {
+            if self.distribution.is_smooth() {
+                let (under_coat, under_sheen) = self.transmitted(wo);
+                let reflectance = self.fresnel(wo.z) * (under_coat * under_sheen);
+                (
+                    Vec3::new(-wo.x, -wo.y, wo.z),
+                    Some((reflectance, specular_prob)),
+                )
             } else {
-                (shading_normal + Vec3::<Unnormalized>::random_in_unit_sphere()).normalize()
-            };
-
-        // Apply roughness to whichever scatter model was chosen
-        if roughness > 0.0 {
-            loop {
-                scatter_direction = (scatter_direction
-                    + Vec3::<Unnormalized>::random_in_unit_sphere() * roughness)
-                    .normalize();
-                if scatter_direction.dot(&hit.normal) > 0.0 {
-                    break;
-                }
+                (reflect(wo, &self.distribution.sample_wm(wo, u)), None)
             }
+        } else {
+            let wi = sample_cosine_hemisphere(u);
+            return Some(BsdfSample {
+                wi,
+                f: self.eval(wo, &wi),
+                pdf: self.pdf(wo, &wi),
+                flags: LobeFlags::REFLECTION | LobeFlags::DIFFUSE,
+            });
+        };
+
+        if wi.z <= 0.0 || !wi.is_finite() {
+            return None;
         }
 
-        // Remove shadow acne
-        let origin = hit.point + hit.normal * (hit.t * 1e-4).max(1e-4);
-        let scattered = Ray::new(origin, scatter_direction);
-        (scattered, self.albedo.sample(hit))
+        // Mirror reflection off a smooth coat or base
+        if let Some((reflectance, prob)) = smooth_reflectance {
+            return Some(BsdfSample {
+                wi,
+                f: reflectance * (self.alpha / wi.z),
+                pdf: self.alpha * prob,
+                flags: LobeFlags::REFLECTION | LobeFlags::SPECULAR,
+            });
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            flags: LobeFlags::REFLECTION | LobeFlags::GLOSSY,
+        })
     }
 
-    fn get_name(&self) -> &str {
-        &self.name
+    fn eval(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::zero();
+        }
+        let (under_coat, under_sheen) = self.transmitted(wo);
+        let base = (self.diffuse(wo.z) + self.specular(wo, wi)) * under_sheen;
+        let sheen = self
+            .sheen
+            .as_ref()
+            .map_or(Color::zero(), |sheen| sheen.eval(wo, wi));
+        let coat = self
+            .clearcoat
+            .as_ref()
+            .map_or(Color::zero(), |coat| coat.eval(wo, wi));
+        (coat + (sheen + base) * under_coat) * self.alpha
+    }
+
+    fn pdf(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let [coat_prob, specular_prob, cosine_prob] = self.lobe_probs(wo);
+        let coat_pdf = self.clearcoat.as_ref().map_or(0.0, |coat| coat.pdf(wo, wi));
+        self.alpha
+            * (coat_prob * coat_pdf
+                + specular_prob * self.specular_pdf(wo, wi)
+                + cosine_prob * wi.z * FRAC_1_PI)
+    }
+}
+
+// Dielectric GGX lobe around the clearcoat normal, over everything else
+#[derive(Debug)]
+struct CoatLobe {
+    factor: f32,
+    roughness: f32,
+    distribution: TrowbridgeReitz,
+    frame: Frame, // Around the coat normal, in the shading frame
+}
+
+impl CoatLobe {
+    fn new(factor: f32, roughness: f32, normal: Vec3<Normalized>) -> Self {
+        let roughness = roughness.clamp(0.0, 1.0);
+        Self {
+            factor,
+            roughness,
+            distribution: TrowbridgeReitz::from_roughness(roughness),
+            frame: Frame::from_z(normal),
+        }
+    }
+
+    fn f0() -> Color {
+        let f0 = ior_to_f0(DEFAULT_IOR, 1.0);
+        Color::new(f0, f0, f0)
+    }
+
+    // Fraction of light the coat reflects towards wo, which the layers below lose
+    fn albedo(&self, wo: &Vec3<Normalized>) -> f32 {
+        let cosine = self.frame.to_local(wo).z;
+        self.factor * specular_albedo(Self::f0(), 1.0, self.roughness, cosine).x
+    }
+
+    // Reflectance of the coat as a mirror
+    fn reflectance(&self, wo: &Vec3<Normalized>) -> f32 {
+        self.factor * schlick(Self::f0(), 1.0, self.frame.to_local(wo).z).x
+    }
+
+    fn sample(&self, wo: &Vec3<Normalized>, u: (f32, f32)) -> Vec3<Normalized> {
+        let wo = self.frame.to_local(wo);
+        let wi = if self.distribution.is_smooth() {
+            Vec3::new(-wo.x, -wo.y, wo.z)
+        } else {
+            reflect(&wo, &self.distribution.sample_wm(&wo, u))
+        };
+        self.frame.to_world(&wi)
+    }
+
+    fn eval(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        let (wo, wi) = (self.frame.to_local(wo), self.frame.to_local(wi));
+        if self.distribution.is_smooth() || wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::zero();
+        }
+        let wm = (wo + wi).normalize();
+        schlick(Self::f0(), 1.0, wo.dot(&wm))
+            * (self.factor * self.distribution.d(&wm) * self.distribution.g(&wo, &wi)
+                / (4.0 * wo.z * wi.z))
+    }
+
+    fn pdf(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        let (wo, wi) = (self.frame.to_local(wo), self.frame.to_local(wi));
+        if self.distribution.is_smooth() || wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (wo + wi).normalize();
+        self.distribution.visible_d(&wo, &wm) / (4.0 * wo.dot(&wm).abs())
     }
 }
 
 impl From<LambertianBase<Color, Color>> for LambertianBase<Texture, Texture> {
     fn from(base: LambertianBase<Color, Color>) -> Self {
-        let pixels = vec![base.albedo];
-        let orm = vec![base.orm];
-
         Self {
             name: base.name,
-            albedo: Texture {
-                data: pixels,
-                width: 1,
-                height: 1,
-            },
+            albedo: Texture::constant(base.albedo),
             normal_texture: None,
-            orm: Texture {
-                data: orm,
-                width: 1,
-                height: 1,
-            },
+            orm: Texture::constant(base.orm),
             alpha: base.alpha,
+            emission: base.emission,
+            layers: base.layers,
         }
     }
 }
-
-fn schlick(cosine: f32, ior: f32) -> f32 {
-    let r0 = ((1.0 - ior) / (1.0 + ior)).powi(2);
-    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
-}
@@ -0,0 +1,47 @@
+use crate::texture::Texture;
+
+// glTF PBR extensions on top of the metallic-roughness base. Like the base's orm texture, every
+// input is a texture with its factor already multiplied in, a single texel when there's no map
+#[derive(Debug, Default)]
+pub struct Layers {
+    pub ior: Option<f32>, // Of the dielectric base, 1.5 when not set
+    pub specular: Option<Specular>,
+    pub clearcoat: Option<Clearcoat>,
+    pub sheen: Option<Sheen>,
+    pub iridescence: Option<Iridescence>,
+    pub anisotropy: Option<Anisotropy>,
+}
+
+// Strength and tint of the dielectric reflection
+#[derive(Debug)]
+pub struct Specular {
+    pub factor: Texture,
+    pub color: Texture,
+}
+
+// Smooth varnish over the whole material, with its own normal map
+#[derive(Debug)]
+pub struct Clearcoat {
+    pub factor: Texture,
+    pub roughness: Texture,
+    pub normal_texture: Option<Texture>,
+}
+
+#[derive(Debug)]
+pub struct Sheen {
+    pub color: Texture,
+    pub roughness: Texture,
+}
+
+#[derive(Debug)]
+pub struct Iridescence {
+    pub factor: Texture,
+    pub ior: f32,
+    pub thickness: Texture, // In nanometers
+}
+
+// Stretches the specular highlight along a direction in tangent space
+#[derive(Debug)]
+pub struct Anisotropy {
+    pub direction: Texture, // Unit direction in x and y, strength in z
+}
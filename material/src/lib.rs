@@ -1,11 +1,28 @@
+mod bsdf;
+mod conductor;
 mod dielectric;
+mod emission;
 mod emissive;
+mod interface;
 mod lambertian;
+mod layers;
 mod material_trait;
+mod medium;
+mod microfacet;
+mod sheen;
+mod subsurface;
 mod texture;
+mod thin_film;
 
-pub use dielectric::Dielectric;
+pub use bsdf::{Bsdf, BsdfSample, Bxdf, BxdfType, Frame, LobeFlags};
+pub use conductor::{Conductor, ConductorBxdf, ConductorPreset};
+pub use dielectric::{Dielectric, DielectricBxdf};
+pub use emission::Emission;
 pub use emissive::Emissive;
-pub use lambertian::LambertianBase;
-pub use material_trait::{Material, MaterialType};
-pub use texture::Texture;
+pub use interface::Interface;
+pub use lambertian::{LambertianBase, LambertianBxdf};
+pub use layers::{Anisotropy, Clearcoat, Iridescence, Layers, Sheen, Specular};
+pub use material_trait::{Interior, Material, MaterialType};
+pub use medium::{Density, DensityGrid, HenyeyGreenstein, Medium, MediumEvent};
+pub use subsurface::Subsurface;
+pub use texture::{Texture, TextureFilter, UvTransform, WrapMode};
@@ -1,11 +1,41 @@
 use util::{Color, HitResult, Ray};
 
 use crate::{
-    dielectric::Dielectric, emissive::Emissive, lambertian::LambertianBase, texture::Texture,
+    bsdf::Bsdf, conductor::Conductor, dielectric::Dielectric, emissive::Emissive,
+    interface::Interface, lambertian::LambertianBase, subsurface::Subsurface, texture::Texture,
 };
 
+// What a closed transmissive surface is filled with
+#[derive(Clone, Copy, Debug)]
+pub struct Interior {
+    pub ior: f32,
+    pub absorption: Color,     // Beer-Lambert coefficient per unit of distance
+    pub medium: Option<usize>, // Index of the medium scattering light inside
+    pub priority: u32,         // Where interiors overlap the highest priority one fills the space
+}
+
 pub trait Material: Send + Sync {
-    fn scatter(&self, ray: &Ray, hit_record: &HitResult) -> (Ray, Color);
+    // How light scatters at the hit, None if it doesn't
+    fn bsdf(&self, ray: &Ray, hit_record: &HitResult) -> Option<Bsdf>;
+    // Like bsdf(), for a surface whose interior borders a medium of the given index of
+    // refraction instead of air
+    fn nested_bsdf(&self, ray: &Ray, hit_record: &HitResult, _outside_ior: f32) -> Option<Bsdf> {
+        self.bsdf(ray, hit_record)
+    }
+    fn interior(&self) -> Option<Interior> {
+        None
+    }
+    // Whether the surface only bounds its interior, without scattering light
+    fn is_interface(&self) -> bool {
+        false
+    }
+    fn emitted(&self, _hit_record: &HitResult) -> Color {
+        Color::zero()
+    }
+    // Emission averaged over the surface, zero for materials that don't glow
+    fn average_emission(&self) -> Color {
+        Color::zero()
+    }
     fn get_name(&self) -> &str;
 }
 
@@ -16,15 +46,81 @@ pub enum MaterialType {
     TextureLambertian(LambertianBase<Texture, Texture>),
     Emissive(Emissive),
     Dielectric(Dielectric),
+    Conductor(Conductor),
+    Interface(Interface),
+    Subsurface(Subsurface),
 }
 
 impl Material for MaterialType {
-    fn scatter(&self, ray: &Ray, hit_record: &HitResult) -> (Ray, Color) {
+    fn bsdf(&self, ray: &Ray, hit_record: &HitResult) -> Option<Bsdf> {
+        match self {
+            MaterialType::Lambertian(mat) => mat.bsdf(ray, hit_record),
+            MaterialType::TextureLambertian(mat) => mat.bsdf(ray, hit_record),
+            MaterialType::Emissive(mat) => mat.bsdf(ray, hit_record),
+            MaterialType::Dielectric(mat) => mat.bsdf(ray, hit_record),
+            MaterialType::Conductor(mat) => mat.bsdf(ray, hit_record),
+            MaterialType::Interface(mat) => mat.bsdf(ray, hit_record),
+            MaterialType::Subsurface(mat) => mat.bsdf(ray, hit_record),
+        }
+    }
+
+    fn nested_bsdf(&self, ray: &Ray, hit_record: &HitResult, outside_ior: f32) -> Option<Bsdf> {
+        match self {
+            MaterialType::Lambertian(mat) => mat.nested_bsdf(ray, hit_record, outside_ior),
+            MaterialType::TextureLambertian(mat) => mat.nested_bsdf(ray, hit_record, outside_ior),
+            MaterialType::Emissive(mat) => mat.nested_bsdf(ray, hit_record, outside_ior),
+            MaterialType::Dielectric(mat) => mat.nested_bsdf(ray, hit_record, outside_ior),
+            MaterialType::Conductor(mat) => mat.nested_bsdf(ray, hit_record, outside_ior),
+            MaterialType::Interface(mat) => mat.nested_bsdf(ray, hit_record, outside_ior),
+            MaterialType::Subsurface(mat) => mat.nested_bsdf(ray, hit_record, outside_ior),
+        }
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        match self {
+            MaterialType::Lambertian(mat) => mat.interior(),
+            MaterialType::TextureLambertian(mat) => mat.interior(),
+            MaterialType::Emissive(mat) => mat.interior(),
+            MaterialType::Dielectric(mat) => mat.interior(),
+            MaterialType::Conductor(mat) => mat.interior(),
+            MaterialType::Interface(mat) => mat.interior(),
+            MaterialType::Subsurface(mat) => mat.interior(),
+        }
+    }
+
+    fn is_interface(&self) -> bool {
+        match self {
+            MaterialType::Lambertian(mat) => mat.is_interface(),
+            MaterialType::TextureLambertian(mat) => mat.is_interface(),
+            MaterialType::Emissive(mat) => mat.is_interface(),
+            MaterialType::Dielectric(mat) => mat.is_interface(),
+            MaterialType::Conductor(mat) => mat.is_interface(),
+            MaterialType::Interface(mat) => mat.is_interface(),
+            MaterialType::Subsurface(mat) => mat.is_interface(),
+        }
+    }
+
+    fn emitted(&self, hit_record: &HitResult) -> Color {
+        match self {
+            MaterialType::Lambertian(mat) => mat.emitted(hit_record),
+            MaterialType::TextureLambertian(mat) => mat.emitted(hit_record),
+            MaterialType::Emissive(mat) => mat.emitted(hit_record),
+            MaterialType::Dielectric(mat) => mat.emitted(hit_record),
+            MaterialType::Conductor(mat) => mat.emitted(hit_record),
+            MaterialType::Interface(mat) => mat.emitted(hit_record),
+            MaterialType::Subsurface(mat) => mat.emitted(hit_record),
+        }
+    }
+
+    fn average_emission(&self) -> Color {
         match self {
-            MaterialType::Lambertian(mat) => mat.scatter(ray, hit_record),
-            MaterialType::TextureLambertian(mat) => mat.scatter(ray, hit_record),
-            MaterialType::Emissive(mat) => mat.scatter(ray, hit_record),
-            MaterialType::Dielectric(mat) => mat.scatter(ray, hit_record),
+            MaterialType::Lambertian(mat) => mat.average_emission(),
+            MaterialType::TextureLambertian(mat) => mat.average_emission(),
+            MaterialType::Emissive(mat) => mat.average_emission(),
+            MaterialType::Dielectric(mat) => mat.average_emission(),
+            MaterialType::Conductor(mat) => mat.average_emission(),
+            MaterialType::Interface(mat) => mat.average_emission(),
+            MaterialType::Subsurface(mat) => mat.average_emission(),
         }
     }
 
@@ -34,6 +130,9 @@ impl Material for MaterialType {
             MaterialType::TextureLambertian(mat) => mat.get_name(),
             MaterialType::Emissive(mat) => mat.get_name(),
             MaterialType::Dielectric(mat) => mat.get_name(),
+            MaterialType::Conductor(mat) => mat.get_name(),
+            MaterialType::Interface(mat) => mat.get_name(),
+            MaterialType::Subsurface(mat) => mat.get_name(),
         }
     }
 }
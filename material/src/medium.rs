@@ -0,0 +1,270 @@
+use std::f32::consts::{FRAC_1_PI, TAU};
+
+use util::{Color, Interval, Normalized, Point, Ray, Vec3, sampler::Sampler};
+
+use crate::bsdf::Frame;
+
+// Scattering and absorbing space, like fog or smoke, with coefficients per unit of distance
+#[derive(Debug)]
+pub struct Medium {
+    sigma_a: Color,
+    sigma_s: Color,
+    pub phase: HenyeyGreenstein,
+    density: Density,
+}
+
+#[derive(Debug)]
+pub enum Density {
+    Uniform,
+    Grid(DensityGrid),
+}
+
+pub enum MediumEvent {
+    // The ray scattered at distance t, with the fraction of light it carries there
+    Scatter { t: f32, weight: Color },
+    // The ray made it through, with the fraction of light left
+    Pass { weight: Color },
+    Absorbed,
+}
+
+impl Medium {
+    pub fn new(sigma_a: Color, sigma_s: Color, g: f32, density: Density) -> Self {
+        Self {
+            sigma_a,
+            sigma_s,
+            phase: HenyeyGreenstein::new(g),
+            density,
+        }
+    }
+
+    // Samples where along the ray between t_min and t_max light scatters, by spectral tracking
+    // from "Spectral and Decomposition Tracking for Rendering Heterogeneous Volumes" by Kutz et
+    // al.: tentative collisions are drawn against a majorant of the extinction, and are real or
+    // null in proportion to the light the path carries, the weight fixing up the channels
+    pub fn sample(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        throughput: Color,
+        sampler: &mut impl Sampler,
+    ) -> MediumEvent {
+        let majorant = self.majorant();
+        let Some((t_min, t_max)) = self.clip(ray, t_min, t_max).filter(|_| majorant > 0.0) else {
+            return MediumEvent::Pass {
+                weight: Color::new(1.0, 1.0, 1.0),
+            };
+        };
+
+        let mut weight = Color::new(1.0, 1.0, 1.0);
+        let mut t = t_min;
+        loop {
+            t -= (1.0 - sampler.get_1d()).ln() / majorant;
+            if t >= t_max {
+                return MediumEvent::Pass { weight };
+            }
+
+            let (sigma_s, sigma_n) = self.coefficients(&ray.at(t), majorant);
+            let carried = throughput * weight;
+            let (scatter, null) = (average(sigma_s * carried), average(sigma_n * carried));
+            if scatter + null <= 0.0 {
+                return MediumEvent::Absorbed;
+            }
+
+            let scatter_prob = scatter / (scatter + null);
+            if sampler.get_1d() < scatter_prob {
+                weight = weight * sigma_s / (majorant * scatter_prob);
+                return MediumEvent::Scatter { t, weight };
+            }
+            weight = weight * sigma_n / (majorant * (1.0 - scatter_prob));
+        }
+    }
+
+    // Fraction of light that gets through between t_min and t_max, by ratio tracking unless
+    // there's a closed form
+    pub fn transmittance(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        sampler: &mut impl Sampler,
+    ) -> Color {
+        let sigma_t = self.sigma_a + self.sigma_s;
+        if let Density::Uniform = self.density {
+            let distance = t_max - t_min;
+            return Color::new(
+                (-sigma_t.x * distance).exp(),
+                (-sigma_t.y * distance).exp(),
+                (-sigma_t.z * distance).exp(),
+            );
+        }
+
+        let majorant = self.majorant();
+        let mut transmittance = Color::new(1.0, 1.0, 1.0);
+        let Some((t_min, t_max)) = self.clip(ray, t_min, t_max).filter(|_| majorant > 0.0) else {
+            return transmittance;
+        };
+        let mut t = t_min;
+        loop {
+            t -= (1.0 - sampler.get_1d()).ln() / majorant;
+            if t >= t_max {
+                return transmittance;
+            }
+            let (_, sigma_n) = self.coefficients(&ray.at(t), majorant);
+            transmittance = transmittance * sigma_n / majorant;
+            if transmittance.x.max(transmittance.y).max(transmittance.z) <= 0.0 {
+                return transmittance;
+            }
+        }
+    }
+
+    // Narrows the segment down to where the density can be non-zero
+    fn clip(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        match &self.density {
+            Density::Uniform => Some((t_min, t_max)),
+            Density::Grid(grid) => ray
+                .box_interval(&grid.min, &grid.max, &Interval::new(t_min, t_max))
+                .map(|inside| (inside.min, inside.max)),
+        }
+    }
+
+    // Upper bound of the extinction in every channel and everywhere in the medium
+    fn majorant(&self) -> f32 {
+        let sigma_t = self.sigma_a + self.sigma_s;
+        let max_density = match &self.density {
+            Density::Uniform => 1.0,
+            Density::Grid(grid) => grid.max_value,
+        };
+        sigma_t.x.max(sigma_t.y).max(sigma_t.z) * max_density
+    }
+
+    // Scattering coefficient at a point, and what the majorant adds over its extinction
+    fn coefficients(&self, point: &Point, majorant: f32) -> (Color, Color) {
+        let density = match &self.density {
+            Density::Uniform => 1.0,
+            Density::Grid(grid) => grid.lookup(point),
+        };
+        let sigma_t = (self.sigma_a + self.sigma_s) * density;
+        let sigma_n = Color::new(
+            (majorant - sigma_t.x).max(0.0),
+            (majorant - sigma_t.y).max(0.0),
+            (majorant - sigma_t.z).max(0.0),
+        );
+        (self.sigma_s * density, sigma_n)
+    }
+}
+
+// Densities on the corners of a regular grid of cells stretched over a box
+#[derive(Debug)]
+pub struct DensityGrid {
+    data: Vec<f32>, // x varies fastest, then y, then z
+    size: [usize; 3],
+    min: Point,
+    max: Point,
+    max_value: f32,
+}
+
+impl DensityGrid {
+    pub fn new(data: Vec<f32>, size: [usize; 3], min: Point, max: Point) -> Self {
+        assert_eq!(
+            data.len(),
+            size[0] * size[1] * size[2],
+            "Density grid doesn't match its size"
+        );
+        let max_value = data.iter().copied().fold(0.0, f32::max);
+        Self {
+            data,
+            size,
+            min,
+            max,
+            max_value,
+        }
+    }
+
+    // Trilinear interpolation, zero outside the box
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn lookup(&self, point: &Point) -> f32 {
+        let extent = self.max - self.min;
+        let local = [
+            (point.x - self.min.x) / extent.x,
+            (point.y - self.min.y) / extent.y,
+            (point.z - self.min.z) / extent.z,
+        ];
+        if local.iter().any(|v| !(0.0..=1.0).contains(v)) {
+            return 0.0;
+        }
+
+        let mut corner = [0; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let x = local[axis] * (self.size[axis] - 1) as f32;
+            corner[axis] = (x as usize).min(self.size[axis].saturating_sub(2));
+            frac[axis] = x - corner[axis] as f32;
+        }
+
+        let at = |dx: usize, dy: usize, dz: usize| {
+            let x = (corner[0] + dx).min(self.size[0] - 1);
+            let y = (corner[1] + dy).min(self.size[1] - 1);
+            let z = (corner[2] + dz).min(self.size[2] - 1);
+            self.data[(z * self.size[1] + y) * self.size[0] + x]
+        };
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let plane = |dz: usize| {
+            lerp(
+                lerp(at(0, 0, dz), at(1, 0, dz), frac[0]),
+                lerp(at(0, 1, dz), at(1, 1, dz), frac[0]),
+                frac[1],
+            )
+        };
+        lerp(plane(0), plane(1), frac[2])
+    }
+}
+
+// Phase function where g > 0 scatters forward and g < 0 backward
+#[derive(Clone, Copy, Debug)]
+pub struct HenyeyGreenstein {
+    g: f32,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f32) -> Self {
+        Self {
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    // Density of scattering towards wi, with wo and wi pointing away from the point
+    pub fn p(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        self.eval(wo.dot(wi))
+    }
+
+    pub fn sample(&self, wo: &Vec3<Normalized>, u: (f32, f32)) -> (Vec3<Normalized>, f32) {
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u.0
+        } else {
+            -(1.0 + g * g - ((1.0 - g * g) / (1.0 + g - 2.0 * g * u.0)).powi(2)) / (2.0 * g)
+        }
+        .clamp(-1.0, 1.0);
+
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = TAU * u.1;
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta).normalize();
+        let wi = Frame::from_z(*wo).to_world(&local);
+        (wi, self.eval(cos_theta))
+    }
+
+    fn eval(self, cos_theta: f32) -> f32 {
+        let g = self.g;
+        let denom = 1.0 + g * g + 2.0 * g * cos_theta;
+        FRAC_1_PI / 4.0 * (1.0 - g * g) / (denom * denom.max(0.0).sqrt())
+    }
+}
+
+fn average(color: Color) -> f32 {
+    (color.x + color.y + color.z) / 3.0
+}
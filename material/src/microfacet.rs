@@ -0,0 +1,116 @@
+use std::f32::consts::PI;
+
+use util::{Color, Normalized, Vec3};
+
+// Below this the lobe is too narrow to evaluate in f32 and is treated as a perfect mirror
+const SMOOTH_ALPHA: f32 = 1e-3;
+
+// GGX / Trowbridge-Reitz microfacet distribution, directions in the local shading frame
+#[derive(Debug)]
+pub struct TrowbridgeReitz {
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f32, alpha_y: f32) -> Self {
+        Self { alpha_x, alpha_y }
+    }
+
+    // Perceptually linear roughness as used by glTF, alpha = roughness²
+    pub fn from_roughness(roughness: f32) -> Self {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        Self::new(alpha, alpha)
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < SMOOTH_ALPHA
+    }
+
+    // Density of microfacet normals
+    pub fn d(&self, wm: &Vec3<Normalized>) -> f32 {
+        let cos2_theta = wm.z * wm.z;
+        if cos2_theta <= 0.0 {
+            return 0.0;
+        }
+        let e = ((wm.x / self.alpha_x).powi(2) + (wm.y / self.alpha_y).powi(2)) / cos2_theta;
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos2_theta * cos2_theta * (1.0 + e).powi(2))
+    }
+
+    // Smith auxiliary function, the ratio of hidden to visible microfacet area seen from w
+    fn lambda(&self, w: &Vec3<Normalized>) -> f32 {
+        if w.z == 0.0 {
+            return f32::INFINITY;
+        }
+        let alpha2_tan2 =
+            ((w.x * self.alpha_x).powi(2) + (w.y * self.alpha_y).powi(2)) / (w.z * w.z);
+        ((1.0 + alpha2_tan2).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: &Vec3<Normalized>) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated masking and shadowing
+    pub fn g(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of the normals visible from w, which sample_wm() draws from
+    pub fn visible_d(&self, w: &Vec3<Normalized>, wm: &Vec3<Normalized>) -> f32 {
+        self.g1(w) / w.z.abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    // Visible normal sampling from "Sampling the GGX Distribution of Visible Normals" by Heitz
+    pub fn sample_wm(&self, w: &Vec3<Normalized>, u: (f32, f32)) -> Vec3<Normalized> {
+        // Stretch w so the distribution becomes a hemisphere
+        let mut wh = Vec3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).normalize();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+
+        let t1 = if wh.z < 0.99999 {
+            Vec3::cross(&Vec3::<Normalized>::new(0.0, 0.0, 1.0), &wh).normalize()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(&wh, &t1);
+
+        // Uniform point on the projected hemisphere, warped towards the visible half
+        let disk = Vec3::sample_in_unit_disk(u);
+        let h = (1.0 - disk.x * disk.x).sqrt();
+        let t = f32::midpoint(1.0, wh.z);
+        let py = (1.0 - t) * h + t * disk.y;
+        let pz = (1.0 - disk.x * disk.x - py * py).max(0.0).sqrt();
+        let nh = t1 * disk.x + t2 * py + wh * pz;
+
+        // Unstretch back to the ellipsoid
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).normalize()
+    }
+}
+
+pub fn reflect(wo: &Vec3<Normalized>, wm: &Vec3<Normalized>) -> Vec3<Normalized> {
+    (-*wo + *wm * (2.0 * wo.dot(wm))).normalize()
+}
+
+// Fresnel reflectance going from f0 at normal incidence to f90 at grazing angles
+pub fn schlick(f0: Color, f90: f32, cosine: f32) -> Color {
+    let weight = (1.0 - cosine.clamp(0.0, 1.0)).powi(5);
+    f0 + (Color::new(f90, f90, f90) - f0) * weight
+}
+
+// Reflectance at normal incidence of an interface between two indices of refraction
+pub fn ior_to_f0(ior: f32, outside_ior: f32) -> f32 {
+    ((ior - outside_ior) / (ior + outside_ior)).powi(2)
+}
+
+// Fraction of light a GGX lobe reflects towards a direction at the given cosine, from the fit by
+// Karis in "Physically Based Shading on Mobile"
+pub fn specular_albedo(f0: Color, f90: f32, roughness: f32, cosine: f32) -> Color {
+    let falloff = 1.0 - roughness;
+    let a004 =
+        (falloff * falloff).min((-9.28 * cosine).exp2()) * falloff + 0.0425 - 0.0275 * roughness;
+    let scale = -1.04 * a004 + 1.04 - 0.572 * roughness;
+    let bias = 1.04 * a004 - 0.04 + 0.022 * roughness;
+    f0 * scale + Color::new(f90 * bias, f90 * bias, f90 * bias)
+}
@@ -0,0 +1,112 @@
+#![allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::many_single_char_names
+)]
+
+use std::{
+    f32::consts::{FRAC_1_PI, TAU},
+    sync::LazyLock,
+};
+
+use util::{Color, Normalized, Vec3};
+
+// Below this the lobe is a rim too thin to be seen
+const MIN_ROUGHNESS: f32 = 0.07;
+const ALBEDO_SIZE: usize = 32;
+
+// Fraction of light a white sheen lobe reflects, by roughness and cosine with the normal
+static ALBEDO: LazyLock<Vec<f32>> = LazyLock::new(|| {
+    let steps = 64;
+    (0..ALBEDO_SIZE * ALBEDO_SIZE)
+        .map(|index| {
+            let roughness = (index / ALBEDO_SIZE) as f32 / (ALBEDO_SIZE - 1) as f32;
+            let cos_o = ((index % ALBEDO_SIZE) as f32 / (ALBEDO_SIZE - 1) as f32).max(1e-3);
+            let lobe = SheenLobe::new(Color::new(1.0, 1.0, 1.0), roughness);
+            let wo = Vec3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o).normalize();
+
+            // Midpoint rule over the hemisphere, uniform in cosine and angle
+            let mut total = 0.0;
+            for i in 0..steps {
+                let cos_i = (i as f32 + 0.5) / steps as f32;
+                let sin_i = (1.0 - cos_i * cos_i).sqrt();
+                for j in 0..steps {
+                    let phi = TAU * (j as f32 + 0.5) / steps as f32;
+                    let wi = Vec3::new(sin_i * phi.cos(), sin_i * phi.sin(), cos_i).normalize();
+                    total += lobe.eval(&wo, &wi).x * cos_i;
+                }
+            }
+            total * TAU / (steps * steps) as f32
+        })
+        .collect()
+});
+
+// Velvet-like reflection of fabric fibers at grazing angles, with the Charlie distribution from
+// "Production Friendly Microfacet Sheen BRDF" by Estevez and Kulla
+#[derive(Debug)]
+pub struct SheenLobe {
+    color: Color,
+    roughness: f32,
+    alpha: f32,
+}
+
+impl SheenLobe {
+    pub fn new(color: Color, roughness: f32) -> Self {
+        let roughness = roughness.clamp(MIN_ROUGHNESS, 1.0);
+        Self {
+            color,
+            roughness,
+            alpha: roughness * roughness,
+        }
+    }
+
+    pub fn eval(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::zero();
+        }
+        let wm = (*wo + *wi).normalize();
+        let inv_alpha = 1.0 / self.alpha;
+        let sin2_theta = (1.0 - wm.z * wm.z).max(0.0);
+        let d = (2.0 + inv_alpha) * sin2_theta.powf(inv_alpha * 0.5) * FRAC_1_PI / 2.0;
+
+        let visibility = 1.0 / ((1.0 + self.lambda(wo.z) + self.lambda(wi.z)) * 4.0 * wo.z * wi.z);
+        self.color * (d * visibility.clamp(0.0, 1.0))
+    }
+
+    // Fraction of light reflected towards a direction at the given cosine, for the strongest
+    // channel, which the layers below lose
+    pub fn albedo(&self, cosine: f32) -> f32 {
+        let strength = self.color.x.max(self.color.y).max(self.color.z);
+        let x = cosine.clamp(0.0, 1.0) * (ALBEDO_SIZE - 1) as f32;
+        let y = self.roughness * (ALBEDO_SIZE - 1) as f32;
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = ((x0 + 1).min(ALBEDO_SIZE - 1), (y0 + 1).min(ALBEDO_SIZE - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+        let at = |x: usize, y: usize| ALBEDO[y * ALBEDO_SIZE + x];
+        let bottom = at(x0, y0) * (1.0 - tx) + at(x1, y0) * tx;
+        let top = at(x0, y1) * (1.0 - tx) + at(x1, y1) * tx;
+        strength * (bottom * (1.0 - ty) + top * ty)
+    }
+
+    // Fitted shadowing of the fibers
+    fn lambda(&self, cosine: f32) -> f32 {
+        if cosine < 0.5 {
+            self.lambda_fit(cosine).exp()
+        } else {
+            (2.0 * self.lambda_fit(0.5) - self.lambda_fit(1.0 - cosine)).exp()
+        }
+    }
+
+    fn lambda_fit(&self, x: f32) -> f32 {
+        let t = (1.0 - self.alpha) * (1.0 - self.alpha);
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let a = lerp(21.5473, 25.3245);
+        let b = lerp(3.82987, 3.32435);
+        let c = lerp(0.19823, 0.16801);
+        let d = lerp(-1.97760, -1.27393);
+        let e = lerp(-4.32054, -4.85967);
+        a / (1.0 + b * x.powf(c)) + d * x + e
+    }
+}
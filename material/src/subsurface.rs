@@ -0,0 +1,177 @@
+use util::{Color, HitResult, Ray};
+
+use crate::{
+    bsdf::{Bsdf, BxdfType},
+    dielectric::DielectricBxdf,
+    emission::Emission,
+    lambertian::{Albedo, normal_mapped},
+    material_trait::{Interior, Material},
+    medium::{Density, Medium},
+    microfacet::TrowbridgeReitz,
+    texture::Texture,
+};
+
+// Translucent material like skin, wax or marble: a dielectric surface over a scattering medium
+// that paths random walk through, with coefficients picked so the walks come out with the albedo
+#[derive(Debug)]
+pub struct Subsurface {
+    name: String,
+    albedo: Texture,
+    average_albedo: Color,
+    mean_free_path: Color, // Roughly how far light travels inside, per channel
+    ior: f32,
+    roughness: f32,
+    g: f32,
+    transmission_factor: f32, // The rest is reflected diffusely without entering
+    normal_texture: Option<Texture>,
+    emission: Option<Emission>,
+    medium: Option<usize>,
+    priority: u32,
+}
+
+impl Subsurface {
+    pub fn new(
+        name: String,
+        albedo: Texture,
+        mean_free_path: Color,
+        ior: f32,
+        roughness: f32,
+        g: f32,
+    ) -> Self {
+        Self {
+            name,
+            average_albedo: albedo.average(),
+            albedo,
+            mean_free_path,
+            ior,
+            roughness,
+            g,
+            transmission_factor: 1.0,
+            normal_texture: None,
+            emission: None,
+            medium: None,
+            priority: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_transmission_factor(self, transmission_factor: f32) -> Self {
+        Self {
+            transmission_factor,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_normal_texture(self, normal_texture: Option<Texture>) -> Self {
+        Self {
+            normal_texture,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_emission(self, emission: Option<Emission>) -> Self {
+        Self { emission, ..self }
+    }
+
+    // Inverts the albedo of multiple scattering to the single scattering one, after "Practical
+    // and Controllable Subsurface Scattering for Production Path Tracing" by Chiang et al.
+    pub fn medium(&self) -> Medium {
+        let coefficients = |albedo: f32, distance: f32| {
+            let albedo = albedo.clamp(0.0, 0.999);
+            let single = 1.0
+                - (-5.09406 * albedo + 2.61188 * albedo.powi(2) - 4.31805 * albedo.powi(3)).exp();
+            let scale = 1.9 - albedo + 3.5 * (albedo - 0.8).powi(2);
+            let sigma_t = 1.0 / (distance.max(1e-6) * scale);
+            (sigma_t * (1.0 - single), sigma_t * single)
+        };
+        let (a, d) = (self.average_albedo, self.mean_free_path);
+        let [(ax, sx), (ay, sy), (az, sz)] = [
+            coefficients(a.x, d.x),
+            coefficients(a.y, d.y),
+            coefficients(a.z, d.z),
+        ];
+        Medium::new(
+            Color::new(ax, ay, az),
+            Color::new(sx, sy, sz),
+            self.g,
+            Density::Uniform,
+        )
+    }
+
+    pub fn set_medium(&mut self, medium: usize) {
+        self.medium = Some(medium);
+    }
+
+    pub fn set_priority(&mut self, priority: u32) {
+        self.priority = priority;
+    }
+
+    pub fn take_emission(&mut self) -> Option<Emission> {
+        self.emission.take()
+    }
+}
+
+impl Material for Subsurface {
+    fn bsdf(&self, ray: &Ray, hit: &HitResult) -> Option<Bsdf> {
+        self.nested_bsdf(ray, hit, 1.0)
+    }
+
+    fn nested_bsdf(&self, _ray: &Ray, hit: &HitResult, outside_ior: f32) -> Option<Bsdf> {
+        let ri = if hit.front_face {
+            outside_ior / self.ior
+        } else {
+            self.ior / outside_ior
+        };
+
+        // The medium gives the average color, so light is only tinted on the way in by how the
+        // texture differs from it. The part that doesn't go in looks like the walks coming back
+        // out, a diffuse surface of the albedo. From inside everything leaves
+        let (tint, transmission_factor, albedo) = if hit.front_face {
+            let albedo = self.albedo.sample(hit);
+            let tint = albedo / Color::max(&self.average_albedo, &Color::new(1e-4, 1e-4, 1e-4));
+            (tint, self.transmission_factor, albedo)
+        } else {
+            (Color::new(1.0, 1.0, 1.0), 1.0, Color::zero())
+        };
+
+        let bxdf = DielectricBxdf::new(
+            tint,
+            ri,
+            transmission_factor,
+            TrowbridgeReitz::from_roughness(self.roughness),
+        )
+        .with_opaque_albedo(albedo);
+        Some(Bsdf::new(
+            normal_mapped(self.normal_texture.as_ref(), hit),
+            hit.normal,
+            BxdfType::Dielectric(bxdf),
+        ))
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        Some(Interior {
+            ior: self.ior,
+            absorption: Color::zero(),
+            medium: self.medium,
+            priority: self.priority,
+        })
+    }
+
+    fn emitted(&self, hit: &HitResult) -> Color {
+        self.emission
+            .as_ref()
+            .map_or(Color::zero(), |emission| emission.sample(hit))
+    }
+
+    fn average_emission(&self) -> Color {
+        self.emission
+            .as_ref()
+            .map_or(Color::zero(), Emission::average)
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
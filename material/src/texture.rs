@@ -1,8 +1,386 @@
-use util::Color;
+#![allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_possible_wrap
+)]
+
+use util::{Color, HitResult, Vec3};
+
+// Most probes an anisotropic lookup takes along the long axis of its footprint
+const MAX_ANISOTROPY: f32 = 16.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+    Trilinear,
+    #[default]
+    Anisotropic, // Gaussian weighted trilinear probes along the footprint, approximating EWA
+}
+
+// What lookups outside [0, 1] read, like glTF's wrapS and wrapT
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+// Scale, then counter-clockwise rotation in radians, then offset, like KHR_texture_transform
+#[derive(Clone, Copy, Debug)]
+pub struct UvTransform {
+    pub offset: (f32, f32),
+    pub rotation: f32,
+    pub scale: (f32, f32),
+}
 
 #[derive(Debug)]
 pub struct Texture {
-    pub data: Vec<Color>,
-    pub width: usize,
-    pub height: usize,
+    levels: Vec<MipLevel>, // Full resolution first, each next one half the size down to 1x1
+    pub filter: TextureFilter,
+    pub wrap: (WrapMode, WrapMode), // Along u and v
+    pub tex_coord: usize,           // Which uv set of the mesh to read
+    pub transform: Option<UvTransform>,
+}
+
+#[derive(Debug)]
+struct MipLevel {
+    data: Vec<Color>,
+    width: usize,
+    height: usize,
+}
+
+impl Texture {
+    pub fn new(data: Vec<Color>, width: usize, height: usize) -> Self {
+        assert_eq!(
+            data.len(),
+            width * height,
+            "Texture size doesn't match its texels"
+        );
+        let mut levels = vec![MipLevel {
+            data,
+            width,
+            height,
+        }];
+        while let Some(level) = levels.last()
+            && (level.width > 1 || level.height > 1)
+        {
+            levels.push(level.downsampled());
+        }
+
+        Self {
+            levels,
+            filter: TextureFilter::default(),
+            wrap: (WrapMode::Repeat, WrapMode::Repeat),
+            tex_coord: 0,
+            transform: None,
+        }
+    }
+
+    // Single texel texture, which samples the same everywhere
+    pub fn constant(color: Color) -> Self {
+        Self::new(vec![color], 1, 1)
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    pub fn texels(&self) -> &[Color] {
+        &self.levels[0].data
+    }
+
+    pub fn average(&self) -> Color {
+        let sum = self
+            .texels()
+            .iter()
+            .fold(Color::zero(), |sum, texel| sum + *texel);
+        sum / self.texels().len().max(1) as f32
+    }
+
+    // Same size texture with f applied to every texel, and its mip levels rebuilt from them
+    #[must_use]
+    pub fn map(self, f: impl Fn(Color) -> Color) -> Self {
+        let base = &self.levels[0];
+        let data = base.data.iter().copied().map(f).collect();
+        Self {
+            levels: Self::new(data, base.width, base.height).levels,
+            ..self
+        }
+    }
+
+    // Multiplies every texel by factor, like glTF does with the factors of its textures
+    #[must_use]
+    pub fn scaled(self, factor: Color) -> Self {
+        self.map(|texel| texel * factor)
+    }
+
+    // Filtered lookup where the hit's uv set and footprint land on the texture
+    pub fn sample_hit(&self, hit: &HitResult) -> Color {
+        let tex_coord = hit.tex_coords[self.tex_coord.min(1)];
+        let (du, dv) = tex_coord.gradients;
+        let to_uv = |axis: Vec3| (du.dot(&axis), dv.dot(&axis));
+        let (major, minor) = (to_uv(hit.footprint.0), to_uv(hit.footprint.1));
+
+        match self.transform {
+            Some(transform) => {
+                let (u, v) = transform.apply((tex_coord.u, tex_coord.v));
+                let major = transform.apply_linear(major);
+                let minor = transform.apply_linear(minor);
+                self.lookup(u, v, (major, minor))
+            }
+            None => self.lookup(tex_coord.u, tex_coord.v, (major, minor)),
+        }
+    }
+
+    // Filtered lookup at (u, v) over the uv ellipse with the given half axes. A zero footprint
+    // reads the full resolution level
+    fn lookup(&self, u: f32, v: f32, footprint: ((f32, f32), (f32, f32))) -> Color {
+        let base = &self.levels[0];
+        let texel_length =
+            |axis: (f32, f32)| (axis.0 * base.width as f32).hypot(axis.1 * base.height as f32);
+        let (major, minor) = if texel_length(footprint.0) >= texel_length(footprint.1) {
+            footprint
+        } else {
+            (footprint.1, footprint.0)
+        };
+        let (major_length, minor_length) = (texel_length(major), texel_length(minor));
+
+        match self.filter {
+            TextureFilter::Nearest => base.nearest(u, v, self.wrap),
+            TextureFilter::Bilinear => base.bilinear(u, v, self.wrap),
+            TextureFilter::Trilinear => self.trilinear(u, v, 2.0 * major_length),
+            TextureFilter::Anisotropic => {
+                if major_length <= 0.0 {
+                    return base.bilinear(u, v, self.wrap);
+                }
+                // Probes sized to the short axis, spread along the long one
+                let ratio = (major_length / minor_length.max(1e-6)).min(MAX_ANISOTROPY);
+                let probes = ratio.ceil() as usize;
+                let width = 2.0 * major_length / ratio;
+
+                let (mut sum, mut total_weight) = (Color::zero(), 0.0);
+                for i in 0..probes {
+                    let offset = (i as f32 + 0.5) / probes as f32 * 2.0 - 1.0;
+                    let weight = (-2.0 * offset * offset).exp();
+                    let (u, v) = (u + major.0 * offset, v + major.1 * offset);
+                    sum = sum + self.trilinear(u, v, width) * weight;
+                    total_weight += weight;
+                }
+                sum / total_weight
+            }
+        }
+    }
+
+    // Blends the two levels whose texels are closest to width, given in full resolution texels
+    fn trilinear(&self, u: f32, v: f32, width: f32) -> Color {
+        let top = (self.levels.len() - 1) as f32;
+        let level = width.max(1.0).log2().min(top);
+        let fine = level.floor();
+        let t = level - fine;
+
+        let fine_color = self.levels[fine as usize].bilinear(u, v, self.wrap);
+        if t <= 0.0 {
+            return fine_color;
+        }
+        let coarse_color = self.levels[fine as usize + 1].bilinear(u, v, self.wrap);
+        fine_color * (1.0 - t) + coarse_color * t
+    }
+}
+
+impl MipLevel {
+    // Half size level, each texel the box filtered average of those it covers
+    fn downsampled(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let span = |i: usize, size: usize, new_size: usize| {
+            let start = i * size / new_size;
+            start..((i + 1) * size / new_size).max(start + 1)
+        };
+
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (mut sum, mut count) = (Color::zero(), 0);
+                for sy in span(y, self.height, height) {
+                    for sx in span(x, self.width, width) {
+                        sum = sum + self.data[sy * self.width + sx];
+                        count += 1;
+                    }
+                }
+                data.push(sum / count as f32);
+            }
+        }
+
+        Self {
+            data,
+            width,
+            height,
+        }
+    }
+
+    // Texel at integer coordinates, wrapped into the level along each axis
+    fn texel(&self, x: isize, y: isize, wrap: (WrapMode, WrapMode)) -> Color {
+        let x = wrap.0.apply(x, self.width);
+        let y = wrap.1.apply(y, self.height);
+        self.data[y * self.width + x]
+    }
+
+    fn nearest(&self, u: f32, v: f32, wrap: (WrapMode, WrapMode)) -> Color {
+        let x = (u * self.width as f32).floor() as isize;
+        let y = (v * self.height as f32).floor() as isize;
+        self.texel(x, y, wrap)
+    }
+
+    fn bilinear(&self, u: f32, v: f32, wrap: (WrapMode, WrapMode)) -> Color {
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let texel = |x, y| self.texel(x, y, wrap);
+        let top = texel(x0, y0) * (1.0 - tx) + texel(x0 + 1, y0) * tx;
+        let bottom = texel(x0, y0 + 1) * (1.0 - tx) + texel(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+impl WrapMode {
+    // Index into [0, size) for a texel coordinate anywhere
+    fn apply(self, i: isize, size: usize) -> usize {
+        let size = size as isize;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::MirroredRepeat => {
+                let i = i.rem_euclid(2 * size);
+                if i < size { i } else { 2 * size - 1 - i }
+            }
+            WrapMode::ClampToEdge => i.clamp(0, size - 1),
+        };
+        i as usize
+    }
+}
+
+impl UvTransform {
+    fn apply(&self, uv: (f32, f32)) -> (f32, f32) {
+        let (u, v) = self.apply_linear(uv);
+        (u + self.offset.0, v + self.offset.1)
+    }
+
+    // Without the offset, for footprint axes
+    fn apply_linear(&self, uv: (f32, f32)) -> (f32, f32) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (u, v) = (uv.0 * self.scale.0, uv.1 * self.scale.1);
+        (cos * u + sin * v, cos * v - sin * u)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(value: f32) -> Color {
+        Color::new(value, value, value)
+    }
+
+    fn assert_gray(colors: &[Color], values: &[f32]) {
+        assert_eq!(colors.len(), values.len());
+        for (color, value) in colors.iter().zip(values) {
+            assert!(
+                (color.x - value).abs() < 1e-5
+                    && (color.y - value).abs() < 1e-5
+                    && (color.z - value).abs() < 1e-5,
+                "{color:?} is not {value}"
+            );
+        }
+    }
+
+    // 4x2 texture whose columns go 0, 1, 2, 3 and rows add 0 or 4
+    fn ramp() -> Texture {
+        Texture::new((0..8).map(|i| gray(i as f32)).collect(), 4, 2)
+    }
+
+    #[test]
+    fn mip_levels_halve_down_to_one_texel() {
+        let texture = ramp();
+        let sizes: Vec<_> = texture
+            .levels
+            .iter()
+            .map(|level| (level.width, level.height))
+            .collect();
+        assert_eq!(sizes, [(4, 2), (2, 1), (1, 1)]);
+        assert_gray(&texture.levels[1].data, &[2.5, 4.5]);
+        assert_gray(&texture.levels[2].data, &[3.5]);
+    }
+
+    #[test]
+    fn odd_sizes_downsample_over_every_texel() {
+        let texture = Texture::new((0..9).map(|i| gray(i as f32)).collect(), 3, 3);
+        assert_eq!(texture.levels.len(), 2);
+        assert_gray(&texture.levels[1].data, &[4.0]);
+    }
+
+    #[test]
+    fn zero_footprint_reads_full_resolution() {
+        let texture = ramp();
+        let color = texture.lookup(0.375, 0.25, ((0.0, 0.0), (0.0, 0.0)));
+        assert_gray(&[color], &[1.0]);
+    }
+
+    #[test]
+    fn wide_footprint_reads_coarsest_level() {
+        let texture = ramp();
+        let color = texture.lookup(0.5, 0.5, ((4.0, 0.0), (0.0, 4.0)));
+        assert_gray(&[color], &[3.5]);
+    }
+
+    #[test]
+    fn wrap_modes_fold_indices_into_the_texture() {
+        let indices = |wrap: WrapMode| (-4..8).map(|i| wrap.apply(i, 3)).collect::<Vec<_>>();
+        assert_eq!(
+            indices(WrapMode::Repeat),
+            [2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1]
+        );
+        assert_eq!(
+            indices(WrapMode::MirroredRepeat),
+            [2, 2, 1, 0, 0, 1, 2, 2, 1, 0, 0, 1]
+        );
+        assert_eq!(
+            indices(WrapMode::ClampToEdge),
+            [0, 0, 0, 0, 0, 1, 2, 2, 2, 2, 2, 2]
+        );
+    }
+
+    #[test]
+    fn nearest_lookups_outside_the_texture_wrap_per_axis() {
+        let mut texture = ramp();
+        texture.filter = TextureFilter::Nearest;
+        texture.wrap = (WrapMode::ClampToEdge, WrapMode::Repeat);
+        let footprint = ((0.0, 0.0), (0.0, 0.0));
+        assert_gray(&[texture.lookup(1.3, 0.25, footprint)], &[3.0]);
+        assert_gray(&[texture.lookup(-0.2, 1.75, footprint)], &[4.0]);
+    }
+
+    #[test]
+    fn uv_transform_scales_then_rotates_then_offsets() {
+        let transform = UvTransform {
+            offset: (0.5, 0.25),
+            rotation: std::f32::consts::FRAC_PI_2,
+            scale: (2.0, 3.0),
+        };
+        let (u, v) = transform.apply((1.0, 1.0));
+        assert!(
+            (u - 3.5).abs() < 1e-5 && (v + 1.75).abs() < 1e-5,
+            "{u}, {v}"
+        );
+        let (u, v) = transform.apply_linear((1.0, 0.0));
+        assert!(u.abs() < 1e-5 && (v + 2.0).abs() < 1e-5, "{u}, {v}");
+    }
 }
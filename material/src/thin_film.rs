@@ -0,0 +1,94 @@
+#![allow(clippy::unreadable_literal, clippy::similar_names)]
+
+use std::f32::consts::{PI, TAU};
+
+use util::Color;
+
+use crate::microfacet::ior_to_f0;
+
+// Soap bubble or oil slick like film over a surface, which tints its fresnel reflection by
+// interference, after "A Practical Extension to Microfacet Theory for the Modeling of Varying
+// Iridescence" by Belcour and Barla
+#[derive(Debug)]
+pub struct ThinFilm {
+    pub factor: f32,
+    pub ior: f32,
+    pub thickness: f32, // In nanometers
+}
+
+impl ThinFilm {
+    // Reflectance of the film over a base with the given reflectance at normal incidence, seen
+    // from outside at the given cosine
+    pub fn fresnel(&self, cosine: f32, base_f0: Color) -> Color {
+        // The film blends into the air as its thickness goes to zero
+        let t = (self.thickness / 0.03).clamp(0.0, 1.0);
+        let film_ior = 1.0 + (self.ior - 1.0) * t * t * (3.0 - 2.0 * t);
+
+        // Refraction into the film, which may reflect everything
+        let sin2_film = (1.0 - cosine * cosine) / (film_ior * film_ior);
+        if sin2_film >= 1.0 {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        let cos_film = (1.0 - sin2_film).sqrt();
+
+        // Air to film interface
+        let r12 = schlick(ior_to_f0(film_ior, 1.0), cosine);
+        let t121 = 1.0 - r12;
+        let phi21 = if film_ior < 1.0 { 0.0 } else { PI };
+
+        // Film to base interface, per channel
+        let base_ior = [base_f0.x, base_f0.y, base_f0.z].map(|f0| {
+            let sqrt_f0 = f0.clamp(0.0, 0.9999).sqrt();
+            (1.0 + sqrt_f0) / (1.0 - sqrt_f0)
+        });
+        let r23 = base_ior.map(|ior| schlick(ior_to_f0(ior, film_ior), cos_film));
+        let phi = base_ior.map(|ior| phi21 + if ior < film_ior { PI } else { 0.0 });
+
+        // Optical path difference between the light reflected at either interface
+        let opd = 2.0 * film_ior * self.thickness * cos_film;
+
+        // Airy summation, the first term being incoherent and the others interfering
+        let r123 = r23.map(|r23| (r12 * r23).clamp(1e-5, 0.9999));
+        let rs = [0, 1, 2].map(|i| t121 * t121 * r23[i] / (1.0 - r123[i]));
+        let mut total = rs.map(|rs| r12 + rs);
+        let mut cm = rs.map(|rs| rs - t121);
+        for m in [1.0, 2.0] {
+            let shift = phi.map(|phi| m * phi);
+            let sm = sensitivity(m * opd, shift);
+            for i in 0..3 {
+                cm[i] *= r123[i].sqrt();
+                total[i] += cm[i] * 2.0 * sm[i];
+            }
+        }
+        Color::new(total[0].max(0.0), total[1].max(0.0), total[2].max(0.0))
+    }
+}
+
+// Fourier transform of the CIE XYZ sensitivity curves fitted by gaussians, in linear sRGB
+fn sensitivity(opd: f32, shift: [f32; 3]) -> [f32; 3] {
+    let phase = TAU * opd * 1e-9;
+    let val = [5.4856e-13, 4.4201e-13, 5.2481e-13];
+    let pos = [1.6810e+06, 1.7953e+06, 2.2084e+06];
+    let var = [4.3278e+09, 9.3046e+09, 6.6121e+09];
+
+    let mut xyz = [0, 1, 2].map(|i| {
+        val[i]
+            * (TAU * var[i]).sqrt()
+            * (pos[i] * phase + shift[i]).cos()
+            * (-phase * phase * var[i]).exp()
+    });
+    xyz[0] += 9.7470e-14
+        * (TAU * 4.5282e+09f32).sqrt()
+        * (2.2399e+06 * phase + shift[0]).cos()
+        * (-4.5282e+09 * phase * phase).exp();
+    let [x, y, z] = xyz.map(|v| v / 1.0685e-7);
+    [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.969266 * x + 1.8760108 * y + 0.041556 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ]
+}
+
+fn schlick(f0: f32, cosine: f32) -> f32 {
+    f0 + (1.0 - f0) * (1.0 - cosine.clamp(0.0, 1.0)).powi(5)
+}
@@ -1,16 +1,20 @@
 use std::{io::Read, path::Path};
 
 use geometry::HittableType;
-use gltf::{GltfData, MimeType};
-use material::{MaterialType, Texture};
-use util::Color;
+use gltf::{GltfData, GltfTexture, MimeType, Sampler};
+use light::LightType;
+use material::{MaterialType, Texture, TextureFilter, UvTransform, WrapMode};
+use util::{Color, color::srgb_u8_to_linear};
 
 use crate::{
     glb::types::{Chunk, ChunkType, GlbHeader},
     gltf_parser::assemble_scene,
 };
 
-pub fn parse_glb(path: &str, mat_offset: usize) -> (Vec<HittableType>, Vec<MaterialType>) {
+pub fn parse_glb(
+    path: &str,
+    mat_offset: usize,
+) -> (Vec<HittableType>, Vec<MaterialType>, Vec<LightType>) {
     let mut buffer = vec![];
     // Print the absolute path of the file being read
     let abs_path = std::fs::canonicalize(path).unwrap_or_else(|_| std::path::PathBuf::from(path));
@@ -75,13 +79,59 @@ fn parse_chunk(buffer: &[u8], offset: usize) -> Chunk {
     }
 }
 
+// Data like normals, roughness and metalness, which glTF stores linearly
 pub fn load_texture(
     binary: &[&[u8]],
     gltf_data: &GltfData,
-    tex_index: usize,
+    info: &GltfTexture,
     base_path: &Path,
 ) -> Texture {
-    let texture = gltf_data.textures.get(tex_index).unwrap();
+    load_texture_texels(binary, gltf_data, info, base_path, |rgba| {
+        Color::new(
+            f32::from(rgba[0]) / 255.0,
+            f32::from(rgba[1]) / 255.0,
+            f32::from(rgba[2]) / 255.0,
+        )
+    })
+}
+
+// Colors, like the base color and emission, which glTF stores sRGB encoded
+pub fn load_color_texture(
+    binary: &[&[u8]],
+    gltf_data: &GltfData,
+    info: &GltfTexture,
+    base_path: &Path,
+) -> Texture {
+    load_texture_texels(binary, gltf_data, info, base_path, |rgba| {
+        Color::new(
+            srgb_u8_to_linear(rgba[0]),
+            srgb_u8_to_linear(rgba[1]),
+            srgb_u8_to_linear(rgba[2]),
+        )
+    })
+}
+
+// The alpha channel in all three channels, for the inputs glTF keeps there
+pub fn load_alpha_texture(
+    binary: &[&[u8]],
+    gltf_data: &GltfData,
+    info: &GltfTexture,
+    base_path: &Path,
+) -> Texture {
+    load_texture_texels(binary, gltf_data, info, base_path, |rgba| {
+        let alpha = f32::from(rgba[3]) / 255.0;
+        Color::new(alpha, alpha, alpha)
+    })
+}
+
+fn load_texture_texels(
+    binary: &[&[u8]],
+    gltf_data: &GltfData,
+    info: &GltfTexture,
+    base_path: &Path,
+    texel: impl Fn(&[u8]) -> Color,
+) -> Texture {
+    let texture = gltf_data.textures.get(info.index).unwrap();
     let image = gltf_data.images.get(texture.source).unwrap();
 
     let (data, mime_type) = if let Some(uri) = &image.uri {
@@ -123,21 +173,59 @@ pub fn load_texture(
 
     let width = image.width() as usize;
     let height = image.height() as usize;
-    let pixels = image
-        .into_raw()
-        .chunks(4)
-        .map(|rgba| {
-            Color::new(
-                f32::from(rgba[0]) / 255.0,
-                f32::from(rgba[1]) / 255.0,
-                f32::from(rgba[2]) / 255.0,
-            )
-        })
-        .collect();
+    let pixels = image.into_raw().chunks(4).map(texel).collect();
+
+    let mut loaded = Texture::new(pixels, width, height);
+    if let Some(sampler) = texture
+        .sampler
+        .and_then(|index| gltf_data.samplers.get(index))
+    {
+        loaded.filter = texture_filter(sampler);
+        loaded.wrap = (wrap_mode(sampler.wrap_s), wrap_mode(sampler.wrap_t));
+    }
+
+    // KHR_texture_transform can also pick another uv set than the texture itself
+    let transform = info.extensions.texture_transform.as_ref();
+    loaded.tex_coord = transform
+        .and_then(|transform| transform.tex_coord)
+        .or(info.tex_coord)
+        .unwrap_or(0);
+    loaded.transform = transform.map(|transform| {
+        let [offset_u, offset_v] = transform.offset.unwrap_or([0.0, 0.0]);
+        let [scale_u, scale_v] = transform.scale.unwrap_or([1.0, 1.0]);
+        UvTransform {
+            offset: (offset_u as f32, offset_v as f32),
+            rotation: transform.rotation.unwrap_or(0.0) as f32,
+            scale: (scale_u as f32, scale_v as f32),
+        }
+    });
+    loaded
+}
+
+// Magnified nearest neighbour textures stay pixelated, and the richer the minification filter
+// the better the filter that stands in for it
+fn texture_filter(sampler: &Sampler) -> TextureFilter {
+    const NEAREST: i64 = 9728;
+    const LINEAR: i64 = 9729;
+    const NEAREST_MIPMAP_NEAREST: i64 = 9984;
+    const LINEAR_MIPMAP_NEAREST: i64 = 9985;
+    const NEAREST_MIPMAP_LINEAR: i64 = 9986;
+
+    match (sampler.mag_filter, sampler.min_filter) {
+        (Some(NEAREST), None | Some(NEAREST | NEAREST_MIPMAP_NEAREST | NEAREST_MIPMAP_LINEAR)) => {
+            TextureFilter::Nearest
+        }
+        (_, Some(NEAREST | LINEAR)) => TextureFilter::Bilinear,
+        (_, Some(NEAREST_MIPMAP_NEAREST | LINEAR_MIPMAP_NEAREST)) => TextureFilter::Trilinear,
+        _ => TextureFilter::Anisotropic,
+    }
+}
 
-    Texture {
-        data: pixels,
-        width,
-        height,
+fn wrap_mode(wrap: Option<i64>) -> WrapMode {
+    match wrap {
+        None | Some(10497) => WrapMode::Repeat,
+        Some(33648) => WrapMode::MirroredRepeat,
+        Some(33071) => WrapMode::ClampToEdge,
+        Some(other) => panic!("Unknown texture wrap mode: {other}"),
     }
 }
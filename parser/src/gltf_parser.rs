@@ -1,13 +1,23 @@
 use std::{fs::read_to_string, path::Path, sync::Arc};
 
-use geometry::{HittableType, Instance, Parent};
-use gltf::{GltfData, Material, Node, PbrMetallicRoughness};
-use material::{Dielectric, LambertianBase, MaterialType, Texture};
-use util::Vec3;
+use geometry::{HittableType, Instance, Parent, mat4_multiply, trs_matrix};
+use gltf::{
+    GltfData, GltfLight, GltfTexture, LightKind, Material, MaterialExtensions, MaterialsVolume,
+    Node, PbrMetallicRoughness,
+};
+use light::{DirectionalLight, Light, LightType, PointLight, SpotLight};
+use material::{
+    Anisotropy, Clearcoat, Conductor, ConductorPreset, Dielectric, Emission, Iridescence,
+    LambertianBase, Layers, MaterialType, Sheen, Specular, Subsurface, Texture,
+};
+use util::{Color, Vec3};
 
-use crate::glb::glb_parser::load_texture;
+use crate::glb::glb_parser::{load_alpha_texture, load_color_texture, load_texture};
 
-pub fn parse_gltf(path: &str, mat_offset: usize) -> (Vec<HittableType>, Vec<MaterialType>) {
+pub fn parse_gltf(
+    path: &str,
+    mat_offset: usize,
+) -> (Vec<HittableType>, Vec<MaterialType>, Vec<LightType>) {
     let gltf_data = read_to_string(path).expect("Failed to read .gltf file");
     let gltf_data: GltfData = serde_json::from_str(&gltf_data).expect("Failed to parse .gltf file");
 
@@ -35,7 +45,7 @@ pub fn assemble_scene(
     binary_chunk: &[&[u8]],
     mat_offset: usize,
     base_path: &Path,
-) -> (Vec<HittableType>, Vec<MaterialType>) {
+) -> (Vec<HittableType>, Vec<MaterialType>, Vec<LightType>) {
     let scene = gltf_data
         .scenes
         .get(gltf_data.scene)
@@ -68,20 +78,128 @@ pub fn assemble_scene(
         })
         .collect::<Vec<_>>();
 
+    // Nodes holding only a light have no mesh to instance
     let instances: Vec<HittableType> = nodes
         .iter()
-        .filter_map(|node| {
-            parse_node(node, &gltf_data, &instance_bases)
-                .unwrap()
-                .into()
-        })
+        .filter(|node| node.mesh.is_some() || node.children.is_some())
+        .map(|node| parse_node(node, &gltf_data, &instance_bases).unwrap())
         .collect();
 
     println!("Parsed {} instances", instances.len());
 
+    let mut lights = vec![];
+    for node in &nodes {
+        collect_lights(node, IDENTITY, &gltf_data, &mut lights);
+    }
+
+    println!("Parsed {} lights", lights.len());
+
     let materials = parse_materials(gltf_data, binary_chunk, base_path);
 
-    (instances, materials)
+    (instances, materials, lights)
+}
+
+const IDENTITY: [[f64; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+// Lights are flattened into world space since they don't live in the BVH
+fn collect_lights(
+    node: &Node,
+    parent_to_world: [[f64; 4]; 4],
+    gltf_data: &GltfData,
+    lights: &mut Vec<LightType>,
+) {
+    let object_to_world = mat4_multiply(parent_to_world, node_matrix(node));
+
+    if let Some(node_light) = &node.extensions.light {
+        let light = gltf_data
+            .extensions
+            .lights_punctual
+            .as_ref()
+            .and_then(|ext| ext.lights.get(node_light.light))
+            .expect("Light index out of bounds");
+
+        let mut light = build_light(light);
+        light.transform(object_to_world);
+        lights.push(light);
+    }
+
+    for &child_index in node.children.iter().flatten() {
+        let child = gltf_data
+            .nodes
+            .get(child_index)
+            .expect("Child node index out of bounds");
+        collect_lights(child, object_to_world, gltf_data, lights);
+    }
+}
+
+// Built at the node origin pointing down -Z, ready to be transformed
+fn build_light(light: &GltfLight) -> LightType {
+    let position = Vec3::from(0.0);
+    let direction = Vec3::new(0.0, 0.0, -1.0);
+    let color = light.color.map_or(Vec3::from(1.0), Vec3::from);
+    let intensity = light.intensity.unwrap_or(1.0) as f32;
+    let range = light.range.map(|r| r as f32);
+
+    match light.r#type {
+        LightKind::Point => LightType::Point(PointLight {
+            position,
+            color,
+            intensity,
+            range,
+        }),
+        LightKind::Spot => {
+            let spot = light.spot.as_ref();
+            let inner_angle = spot.and_then(|s| s.inner_cone_angle).unwrap_or(0.0);
+            let outer_angle = spot
+                .and_then(|s| s.outer_cone_angle)
+                .unwrap_or(std::f64::consts::FRAC_PI_4);
+
+            LightType::Spot(SpotLight::new(
+                position,
+                direction,
+                color,
+                intensity,
+                inner_angle as f32,
+                outer_angle as f32,
+                range,
+            ))
+        }
+        LightKind::Directional => LightType::Directional(DirectionalLight {
+            direction,
+            color,
+            intensity,
+        }),
+    }
+}
+
+// glTF matrices are column major, ours are row major
+fn node_matrix(node: &Node) -> [[f64; 4]; 4] {
+    node.matrix.map_or_else(
+        || {
+            let rotation = node.rotation.as_ref().map(|r| {
+                let arr: &[f64; 4] = r.as_slice().try_into().unwrap();
+                arr.map(|v| v as f32)
+            });
+            trs_matrix(
+                node.translation.clone().map(Vec3::from),
+                rotation,
+                node.scale.clone().map_or(Vec3::from(1.0), Vec3::from),
+            )
+        },
+        |m| {
+            [
+                [m[0], m[4], m[8], m[12]],
+                [m[1], m[5], m[9], m[13]],
+                [m[2], m[6], m[10], m[14]],
+                [m[3], m[7], m[11], m[15]],
+            ]
+        },
+    )
 }
 
 fn parse_materials(
@@ -107,77 +225,348 @@ fn build_material(
         name,
         normal_texture,
         pbr_metallic_roughness: pbr,
+        emissive_factor,
+        emissive_texture,
         extensions,
         ..
     } = mat;
     let pbr = pbr.unwrap();
-    let load = |index| load_texture(binary_chunk, gltf_data, index, base_path);
-    let normal_texture = normal_texture.map(|tex| load(tex.index));
+    let load = |info: &GltfTexture| load_texture(binary_chunk, gltf_data, info, base_path);
+    let load_color =
+        |info: &GltfTexture| load_color_texture(binary_chunk, gltf_data, info, base_path);
+    let load_alpha =
+        |info: &GltfTexture| load_alpha_texture(binary_chunk, gltf_data, info, base_path);
+    let normal_texture = normal_texture.as_ref().map(load);
+
+    let emission = build_emission(
+        emissive_factor,
+        emissive_texture.as_ref().map(load_color),
+        extensions
+            .emissive_strength
+            .as_ref()
+            .map(|e| e.emissive_strength),
+    );
+
+    let ior = extensions.ior.as_ref().map(|i| i.ior);
+    let transmission_factor = extensions
+        .transmission
+        .as_ref()
+        .map(|t| t.transmission_factor);
+
+    // The factor splits light between the random walk and the diffuse surface over it
+    if let Some(diffuse_transmission) = &extensions.diffuse_transmission
+        && let Some(factor) = diffuse_transmission
+            .diffuse_transmission_factor
+            .filter(|factor| *factor > 0.0)
+    {
+        let color = diffuse_transmission.diffuse_transmission_color_factor;
+        let albedo = base_color_texture(&pbr, load_color).scaled(color.unwrap_or([1.0; 3]).into());
+        let subsurface = build_subsurface(name, &pbr, albedo, ior, extensions.volume.as_ref(), 0.0)
+            .with_transmission_factor(factor as f32)
+            .with_normal_texture(normal_texture)
+            .with_emission(emission);
+        return MaterialType::Subsurface(subsurface);
+    }
 
-    if let Some(transmission_factor) = extensions.transmission.map(|t| t.transmission_factor) {
+    // A transmissive volume only scatters light with KHR_materials_volume_scatter, otherwise it
+    // just absorbs like colored glass
+    if let (Some(transmission_factor), Some(volume), Some(volume_scatter)) = (
+        transmission_factor.filter(|factor| *factor > 0.0),
+        extensions.volume.as_ref(),
+        extensions.volume_scatter.as_ref(),
+    ) {
+        let albedo = color_texture(
+            volume_scatter.multiscatter_color.unwrap_or([0.0, 0.0, 0.0]),
+            volume_scatter
+                .multiscatter_color_texture
+                .as_ref()
+                .map(load_color),
+        );
+        let g = volume_scatter.scatter_anisotropy.unwrap_or(0.0);
+        let subsurface = build_subsurface(name, &pbr, albedo, ior, Some(volume), g)
+            .with_transmission_factor(transmission_factor as f32)
+            .with_normal_texture(normal_texture)
+            .with_emission(emission);
+        return MaterialType::Subsurface(subsurface);
+    }
+
+    if let Some(transmission_factor) = transmission_factor {
         return build_dielectric(
             name,
             &pbr,
-            extensions.ior.map(|i| i.ior),
+            ior,
             transmission_factor,
+            extensions.volume.as_ref(),
+            emission,
+            load,
         );
     }
 
-    match pbr.base_color_texture {
-        Some(ref tex) => {
-            build_textured_lambertian(name, &pbr, load(tex.index), normal_texture, load)
+    // Plain metals named after one get its measured color
+    if normal_texture.is_none()
+        && is_plain_metal(&pbr, &extensions)
+        && let Some(preset) = ConductorPreset::guess(&name)
+    {
+        let roughness = pbr.roughness_factor.unwrap_or(1.0) as f32;
+        let mut conductor = Conductor::from_preset(name, preset, roughness);
+        conductor.emission = emission;
+        return MaterialType::Conductor(conductor);
+    }
+
+    let layers = build_layers(&extensions, &load, &load_color, &load_alpha);
+    if pbr.base_color_texture.is_some() || pbr.metallic_roughness_texture.is_some() {
+        build_textured_lambertian(
+            name,
+            &pbr,
+            normal_texture,
+            emission,
+            layers,
+            load,
+            load_color,
+        )
+    } else {
+        build_solid_lambertian(name, &pbr, normal_texture, emission, layers)
+    }
+}
+
+// Tints, textures and layers have no place in a measured preset, so metals with any of them keep
+// the glTF model
+fn is_plain_metal(pbr: &PbrMetallicRoughness, extensions: &MaterialExtensions) -> bool {
+    let has_layers = extensions.specular.is_some()
+        || extensions.clearcoat.is_some()
+        || extensions.sheen.is_some()
+        || extensions.iridescence.is_some()
+        || extensions.anisotropy.is_some();
+    pbr.metallic_factor.unwrap_or(1.0) >= 1.0
+        && pbr.base_color_texture.is_none()
+        && pbr.metallic_roughness_texture.is_none()
+        && base_color_factor(pbr)[..3].iter().all(|c| *c >= 1.0)
+        && !has_layers
+}
+
+fn build_layers(
+    extensions: &MaterialExtensions,
+    load: &impl Fn(&GltfTexture) -> Texture,
+    load_color: &impl Fn(&GltfTexture) -> Texture,
+    load_alpha: &impl Fn(&GltfTexture) -> Texture,
+) -> Layers {
+    let load = |tex: &Option<GltfTexture>| tex.as_ref().map(load);
+    let load_color = |tex: &Option<GltfTexture>| tex.as_ref().map(load_color);
+    let load_alpha = |tex: &Option<GltfTexture>| tex.as_ref().map(load_alpha);
+
+    let specular = extensions.specular.as_ref().map(|specular| Specular {
+        factor: factor_texture(
+            specular.specular_factor.unwrap_or(1.0),
+            load_alpha(&specular.specular_texture),
+            |texel| texel.x,
+        ),
+        color: color_texture(
+            specular.specular_color_factor.unwrap_or([1.0, 1.0, 1.0]),
+            load_color(&specular.specular_color_texture),
+        ),
+    });
+
+    let clearcoat = extensions.clearcoat.as_ref().map(|clearcoat| Clearcoat {
+        factor: factor_texture(
+            clearcoat.clearcoat_factor.unwrap_or(0.0),
+            load(&clearcoat.clearcoat_texture),
+            |texel| texel.x,
+        ),
+        roughness: factor_texture(
+            clearcoat.clearcoat_roughness_factor.unwrap_or(0.0),
+            load(&clearcoat.clearcoat_roughness_texture),
+            |texel| texel.y,
+        ),
+        normal_texture: load(&clearcoat.clearcoat_normal_texture),
+    });
+
+    let sheen = extensions.sheen.as_ref().map(|sheen| Sheen {
+        color: color_texture(
+            sheen.sheen_color_factor.unwrap_or([0.0, 0.0, 0.0]),
+            load_color(&sheen.sheen_color_texture),
+        ),
+        roughness: factor_texture(
+            sheen.sheen_roughness_factor.unwrap_or(0.0),
+            load_alpha(&sheen.sheen_roughness_texture),
+            |texel| texel.x,
+        ),
+    });
+
+    let iridescence = extensions.iridescence.as_ref().map(|iridescence| {
+        let min = iridescence.iridescence_thickness_minimum.unwrap_or(100.0) as f32;
+        let max = iridescence.iridescence_thickness_maximum.unwrap_or(400.0) as f32;
+        Iridescence {
+            factor: factor_texture(
+                iridescence.iridescence_factor.unwrap_or(0.0),
+                load(&iridescence.iridescence_texture),
+                |texel| texel.x,
+            ),
+            ior: iridescence.iridescence_ior.unwrap_or(1.3) as f32,
+            // Without a texture the film is as thick as it gets
+            thickness: map_texture(
+                load(&iridescence.iridescence_thickness_texture)
+                    .unwrap_or(Texture::constant(Vec3::new(1.0, 1.0, 1.0))),
+                |texel| min + (max - min) * texel.y,
+            ),
+        }
+    });
+
+    // The texture holds a direction in red and green, rotated by the rotation, and a strength
+    // in blue, scaled by the strength
+    let anisotropy = extensions.anisotropy.as_ref().map(|anisotropy| {
+        let strength = anisotropy.anisotropy_strength.unwrap_or(0.0) as f32;
+        let (sin, cos) = (anisotropy.anisotropy_rotation.unwrap_or(0.0) as f32).sin_cos();
+        let texture = load(&anisotropy.anisotropy_texture)
+            .unwrap_or(Texture::constant(Vec3::new(1.0, 0.5, 1.0)));
+        Anisotropy {
+            direction: texture.map(|texel| {
+                let (x, y) = (texel.x * 2.0 - 1.0, texel.y * 2.0 - 1.0);
+                Vec3::new(cos * x - sin * y, sin * x + cos * y, strength * texel.z)
+            }),
         }
-        None => build_solid_lambertian(name, &pbr, normal_texture),
+    });
+
+    Layers {
+        ior: extensions.ior.as_ref().map(|ior| ior.ior as f32),
+        specular,
+        clearcoat,
+        sheen,
+        iridescence,
+        anisotropy,
+    }
+}
+
+// One channel of a texture times its factor, or just the factor without a texture
+fn factor_texture(factor: f64, texture: Option<Texture>, channel: fn(Color) -> f32) -> Texture {
+    let factor = factor as f32;
+    match texture {
+        Some(texture) => map_texture(texture, |texel| factor * channel(texel)),
+        None => Texture::constant(Vec3::new(factor, factor, factor)),
     }
 }
 
+fn color_texture(factor: [f64; 3], texture: Option<Texture>) -> Texture {
+    texture
+        .unwrap_or(Texture::constant(Vec3::new(1.0, 1.0, 1.0)))
+        .scaled(factor.into())
+}
+
+// Grayscale texture from a function of each texel
+fn map_texture(texture: Texture, f: impl Fn(Color) -> f32) -> Texture {
+    texture.map(|texel| {
+        let value = f(texel);
+        Vec3::new(value, value, value)
+    })
+}
+
+// The factor defaults to black, so a texture alone doesn't make a material glow
+fn build_emission(
+    factor: Option<[f64; 3]>,
+    texture: Option<Texture>,
+    strength: Option<f64>,
+) -> Option<Emission> {
+    let color = Vec3::from(factor?) * strength.unwrap_or(1.0) as f32;
+    (color.length_squared() > 0.0).then(|| Emission::new(color, texture))
+}
+
 fn build_dielectric(
     name: String,
     pbr: &PbrMetallicRoughness,
     ior: Option<f64>,
     transmission_factor: f64,
+    volume: Option<&MaterialsVolume>,
+    emission: Option<Emission>,
+    load: impl Fn(&GltfTexture) -> Texture,
 ) -> MaterialType {
     let ior = ior.unwrap_or(1.5);
-    let albedo = pbr
-        .base_color_factor
-        .as_deref()
-        .unwrap_or(&[1.0, 1.0, 1.0, 1.0]);
+    let albedo = base_color_factor(pbr);
+
+    // Frosted when rough, with the roughness in the green channel
+    let roughness = pbr
+        .metallic_roughness_texture
+        .as_ref()
+        .map_or(Texture::constant(Vec3::new(1.0, 1.0, 1.0)), &load)
+        .scaled(orm_factor(pbr));
+
+    // A thickness of zero makes the surface a thin wall. Otherwise the attenuation color is what
+    // remains of white light after the attenuation distance
+    let absorption = volume
+        .filter(|volume| volume.thickness_factor.unwrap_or(0.0) > 0.0)
+        .map(|volume| {
+            let color = volume.attenuation_color.unwrap_or([1.0, 1.0, 1.0]);
+            let distance = volume.attenuation_distance.unwrap_or(f64::INFINITY);
+            Vec3::from(color.map(|channel| -channel.max(1e-6).ln() / distance))
+        });
 
     MaterialType::Dielectric(Dielectric::new(
         name,
         Some(albedo[..3].into()),
         ior as f32,
         transmission_factor as f32,
+        roughness,
+        absorption,
+        emission,
     ))
 }
 
-fn build_textured_lambertian(
+// Light transmitted into the object scatters around inside it, as far as the attenuation
+// distance tinted by the attenuation color, or else the thickness
+fn build_subsurface(
     name: String,
     pbr: &PbrMetallicRoughness,
     albedo: Texture,
+    ior: Option<f64>,
+    volume: Option<&MaterialsVolume>,
+    g: f64,
+) -> Subsurface {
+    let distance = volume.and_then(|volume| {
+        volume
+            .attenuation_distance
+            .map(|distance| {
+                Vec3::from(volume.attenuation_color.unwrap_or([1.0, 1.0, 1.0])) * distance as f32
+            })
+            .or_else(|| {
+                let thickness = volume.thickness_factor.unwrap_or(0.0) as f32;
+                (thickness > 0.0).then(|| Vec3::new(thickness, thickness, thickness))
+            })
+    });
+
+    Subsurface::new(
+        name,
+        albedo,
+        distance.unwrap_or(Vec3::new(1.0, 1.0, 1.0)),
+        ior.unwrap_or(1.5) as f32,
+        orm_factor(pbr).y,
+        g as f32,
+    )
+}
+
+fn build_textured_lambertian(
+    name: String,
+    pbr: &PbrMetallicRoughness,
     normal_texture: Option<Texture>,
-    load: impl Fn(usize) -> Texture,
+    emission: Option<Emission>,
+    layers: Layers,
+    load: impl Fn(&GltfTexture) -> Texture,
+    load_color: impl Fn(&GltfTexture) -> Texture,
 ) -> MaterialType {
-    let roughness_texture = pbr
+    let albedo = base_color_texture(pbr, &load_color);
+
+    // Roughness is in the green channel and metalness in the blue one
+    let orm = pbr
         .metallic_roughness_texture
         .as_ref()
-        .map(|tex| load(tex.index));
-
-    let roughness = roughness_texture.unwrap_or_else(|| {
-        let r = pbr.roughness_factor.map_or(0.8, |r| r as f32);
-        Texture {
-            data: albedo.data.iter().map(|_| Vec3::new(0.0, r, 0.0)).collect(),
-            width: albedo.width,
-            height: albedo.height,
-        }
-    });
+        .map_or(Texture::constant(Vec3::new(1.0, 1.0, 1.0)), &load)
+        .scaled(orm_factor(pbr));
 
     MaterialType::TextureLambertian(LambertianBase {
         name,
         albedo,
         normal_texture,
-        orm: roughness,
+        orm,
         alpha: 1.0,
+        emission,
+        layers,
     })
 }
 
@@ -185,21 +574,48 @@ fn build_solid_lambertian(
     name: String,
     pbr: &PbrMetallicRoughness,
     normal_texture: Option<Texture>,
+    emission: Option<Emission>,
+    layers: Layers,
 ) -> MaterialType {
-    let rgba = pbr
-        .base_color_factor
-        .as_deref()
-        .unwrap_or(&[1.0, 1.0, 1.0, 1.0]);
+    let rgba = base_color_factor(pbr);
 
     MaterialType::Lambertian(LambertianBase {
         name,
         albedo: rgba[..3].into(),
         normal_texture,
-        orm: Vec3::new(1.0, pbr.roughness_factor.unwrap() as f32, 0.0),
+        orm: orm_factor(pbr),
         alpha: rgba[3] as f32,
+        emission,
+        layers,
     })
 }
 
+// The base color texture times its factor, or just the factor without a texture
+fn base_color_texture(
+    pbr: &PbrMetallicRoughness,
+    load_color: impl Fn(&GltfTexture) -> Texture,
+) -> Texture {
+    pbr.base_color_texture
+        .as_ref()
+        .map_or(Texture::constant(Vec3::new(1.0, 1.0, 1.0)), load_color)
+        .scaled(base_color_factor(pbr)[..3].into())
+}
+
+fn base_color_factor(pbr: &PbrMetallicRoughness) -> &[f64] {
+    pbr.base_color_factor
+        .as_deref()
+        .unwrap_or(&[1.0, 1.0, 1.0, 1.0])
+}
+
+// Both factors default to 1, so a material without them is a rough metal
+fn orm_factor(pbr: &PbrMetallicRoughness) -> Vec3 {
+    Vec3::new(
+        1.0,
+        pbr.roughness_factor.unwrap_or(1.0) as f32,
+        pbr.metallic_factor.unwrap_or(1.0) as f32,
+    )
+}
+
 fn parse_parent(node: &Node, gltf_data: &GltfData, instance_bases: &[Arc<HittableType>]) -> Parent {
     let children = node
         .children
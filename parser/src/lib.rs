@@ -2,8 +2,10 @@ mod glb;
 mod gltf_parser;
 mod mtl_parser;
 mod obj_parser;
+mod vol_parser;
 
 pub use glb::glb_parser::parse_glb;
 pub use gltf_parser::parse_gltf;
 pub use mtl_parser::parse_mtl;
 pub use obj_parser::parse_obj;
+pub use vol_parser::parse_vol;
@@ -1,13 +1,18 @@
-use material::{LambertianBase, Material, MaterialType, Texture};
-use util::{Color, Vec3};
+use material::{
+    Conductor, ConductorPreset, Emission, LambertianBase, Layers, Material, MaterialType, Texture,
+};
+use util::{Color, Vec3, color::srgb_u8_to_linear};
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::too_many_lines)]
 pub fn parse_mtl(path: &str) -> Vec<MaterialType> {
     let file = std::fs::read_to_string(path).expect("Failed to read .mtl file");
 
     let mut materials = vec![];
 
     let mut cur_material_name = None;
+    // Whether the current material says it's a metal, and how rough it says it is
+    let mut metal = false;
+    let mut roughness = None;
     for line in file.lines() {
         let parts: Vec<&str> = line.split_whitespace().collect();
         if parts.is_empty() {
@@ -16,6 +21,11 @@ pub fn parse_mtl(path: &str) -> Vec<MaterialType> {
 
         match parts[0] {
             "newmtl" => {
+                if metal && let Some(last) = materials.last_mut() {
+                    promote_to_conductor(last, roughness);
+                }
+                (metal, roughness) = (false, None);
+
                 let name = (*parts.get(1).unwrap_or(&"default")).to_string();
                 cur_material_name = Some(name.clone());
                 // Push a default material for now; we'll update it when we get more info
@@ -25,6 +35,8 @@ pub fn parse_mtl(path: &str) -> Vec<MaterialType> {
                     normal_texture: None,
                     orm: Vec3::new(1.0, 0.8, 0.0),
                     alpha: 1.0,
+                    emission: None,
+                    layers: Layers::default(),
                 }));
             }
             "Kd" => {
@@ -60,18 +72,14 @@ pub fn parse_mtl(path: &str) -> Vec<MaterialType> {
                     .pixels()
                     .map(|p| {
                         Color::new(
-                            f32::from(p[0]) / 255.0,
-                            f32::from(p[1]) / 255.0,
-                            f32::from(p[2]) / 255.0,
+                            srgb_u8_to_linear(p[0]),
+                            srgb_u8_to_linear(p[1]),
+                            srgb_u8_to_linear(p[2]),
                         )
                     })
                     .collect();
 
-                let albedo = Texture {
-                    data: pixels,
-                    width: width as usize,
-                    height: height as usize,
-                };
+                let albedo = Texture::new(pixels, width as usize, height as usize);
 
                 if let Some(name) = cur_material_name.take()
                     && let Some(last) = materials.pop()
@@ -94,6 +102,50 @@ pub fn parse_mtl(path: &str) -> Vec<MaterialType> {
                     }
                 }
             }
+            "Ke" => {
+                // Emission color, only meaningful when non-black
+                if parts.len() < 4 {
+                    continue;
+                }
+                let r: f32 = parts[1].parse().unwrap_or(0.0);
+                let g: f32 = parts[2].parse().unwrap_or(0.0);
+                let b: f32 = parts[3].parse().unwrap_or(0.0);
+                if r.max(g).max(b) <= 0.0 {
+                    continue;
+                }
+                // Glows on top of whatever else the material does, like glTF's emissive
+                let emission = Some(Emission::new(Color::new(r, g, b), None));
+                match materials.last_mut() {
+                    Some(MaterialType::Lambertian(mat)) => mat.emission = emission,
+                    Some(MaterialType::TextureLambertian(mat)) => mat.emission = emission,
+                    Some(MaterialType::Conductor(mat)) => mat.emission = emission,
+                    _ => {}
+                }
+            }
+            "Ns" => {
+                // Phong exponent, converted to the roughness of a lobe about as wide. PBR
+                // roughness takes precedence
+                let Some(exponent) = parts.get(1).and_then(|p| p.parse::<f32>().ok()) else {
+                    continue;
+                };
+                roughness = roughness.or(Some((2.0 / (exponent.max(0.0) + 2.0)).powf(0.25)));
+            }
+            "Pr" => {
+                roughness = parts
+                    .get(1)
+                    .and_then(|p| p.parse::<f32>().ok())
+                    .or(roughness);
+            }
+            "Pm" => {
+                metal |= parts
+                    .get(1)
+                    .and_then(|p| p.parse::<f32>().ok())
+                    .is_some_and(|metallic| metallic >= 1.0);
+            }
+            "illum" => {
+                // Model 3 is the ray traced reflection that metals are exported with
+                metal |= parts.get(1) == Some(&"3");
+            }
             "d" => {
                 if parts.len() < 2 {
                     continue;
@@ -107,5 +159,71 @@ pub fn parse_mtl(path: &str) -> Vec<MaterialType> {
         }
     }
 
+    if metal && let Some(last) = materials.last_mut() {
+        promote_to_conductor(last, roughness);
+    }
+
     materials
 }
+
+// Metals named after one get its measured color instead of the diffuse one
+fn promote_to_conductor(material: &mut MaterialType, roughness: Option<f32>) {
+    let Some(preset) = ConductorPreset::guess(material.get_name()) else {
+        return;
+    };
+    let (name, emission) = match material {
+        MaterialType::Lambertian(mat) => (mat.name.clone(), mat.emission.take()),
+        MaterialType::TextureLambertian(mat) => (mat.name.clone(), mat.emission.take()),
+        _ => return,
+    };
+
+    let mut conductor = Conductor::from_preset(name, preset, roughness.unwrap_or(0.8));
+    conductor.emission = emission;
+    *material = MaterialType::Conductor(conductor);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes the source to a file of its own, since tests run in parallel
+    fn parse(test: &str, source: &str) -> Vec<MaterialType> {
+        let path =
+            std::env::temp_dir().join(format!("mtl_parser_{test}_{}.mtl", std::process::id()));
+        std::fs::write(&path, source).expect("Failed to write test .mtl file");
+        let materials = parse_mtl(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        materials
+    }
+
+    #[test]
+    fn metal_names_alone_stay_diffuse() {
+        let materials = parse("names", "newmtl Marigold\nKd 0.9 0.6 0.1\nillum 2\n");
+        assert!(matches!(materials[0], MaterialType::Lambertian(_)));
+    }
+
+    #[test]
+    fn pm_or_illum_3_promotes_metal_names() {
+        let materials = parse(
+            "promote",
+            "newmtl gold\nKd 0.5 0.5 0.5\nPm 1\nPr 0.2\n\
+             newmtl copper_pipe\nillum 3\n\
+             newmtl plastic\nillum 3\n",
+        );
+        let MaterialType::Conductor(gold) = &materials[0] else {
+            panic!("gold is {:?}", materials[0]);
+        };
+        assert!((gold.roughness - 0.2).abs() < 1e-6);
+        assert!(matches!(materials[1], MaterialType::Conductor(_)));
+        assert!(matches!(materials[2], MaterialType::Lambertian(_)));
+    }
+
+    #[test]
+    fn promoted_metals_keep_their_emission() {
+        let materials = parse("emission", "newmtl hot_copper\nKe 2 1 0\nPm 1\n");
+        let MaterialType::Conductor(copper) = &materials[0] else {
+            panic!("copper is {:?}", materials[0]);
+        };
+        assert!((copper.average_emission().x - 2.0).abs() < 1e-6);
+    }
+}
@@ -1,11 +1,11 @@
 use geometry::{HittableType, Mesh, Tri};
-use material::{LambertianBase, Material, MaterialType};
+use material::{LambertianBase, Layers, Material, MaterialType};
 use util::{Normalized, Point, Vec3};
 
 use crate::mtl_parser::parse_mtl;
 
 #[allow(dead_code, clippy::too_many_lines)]
-pub fn parse_obj(path: &str) -> (Vec<HittableType>, Vec<MaterialType>) {
+pub fn parse_obj(path: &str, mat_offset: usize) -> (Vec<HittableType>, Vec<MaterialType>) {
     let file = std::fs::read_to_string(path).expect("Failed to read .obj file");
     let mut vertices: Vec<Point> = vec![];
     let mut v_normals: Vec<Vec3<Normalized>> = vec![];
@@ -17,6 +17,8 @@ pub fn parse_obj(path: &str) -> (Vec<HittableType>, Vec<MaterialType>) {
         normal_texture: None,
         orm: Vec3::new(1.0, 0.8, 0.0),
         alpha: 1.0,
+        emission: None,
+        layers: Layers::default(),
     })];
 
     let mut current_material_index = 0;
@@ -129,7 +131,7 @@ pub fn parse_obj(path: &str) -> (Vec<HittableType>, Vec<MaterialType>) {
                     normals,
                     uvs,
                     None,
-                    Some(current_material_index),
+                    Some(current_material_index + mat_offset),
                 ));
             }
 
@@ -0,0 +1,106 @@
+use std::io::Read;
+
+// Mitsuba's binary grid format: a "VOL" header with the resolution, channels and bounding box,
+// then little endian float32 values with the channels of each voxel together, and voxels with x
+// varying fastest, then y, then z, at ((z * yres + y) * xres + x) * channels + c. Returns the
+// first channel and the resolution, not the box, since grids are stretched over the object they
+// fill
+pub fn parse_vol(path: &str) -> (Vec<f32>, [usize; 3]) {
+    let mut buffer = vec![];
+    std::fs::File::open(path)
+        .unwrap_or_else(|_| panic!("Failed to open .vol file {path}"))
+        .read_to_end(&mut buffer)
+        .expect("Failed to read .vol file");
+
+    assert!(
+        buffer.len() >= 48 && &buffer[0..3] == b"VOL",
+        "{path} is not a .vol file"
+    );
+    assert!(buffer[3] == 3, "Unsupported .vol version: {}", buffer[3]);
+
+    let int = |offset: usize| i32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap());
+    let float = |offset: usize| f32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap());
+
+    let encoding = int(4);
+    assert!(
+        encoding == 1,
+        "Unsupported .vol encoding {encoding}, expected float32"
+    );
+
+    let size = [int(8), int(12), int(16)].map(|n| {
+        usize::try_from(n)
+            .ok()
+            .filter(|&n| n > 0)
+            .expect("Invalid .vol resolution")
+    });
+    let channels = usize::try_from(int(20))
+        .ok()
+        .filter(|&n| n > 0)
+        .expect("Invalid .vol channel count");
+
+    let count = size[0] * size[1] * size[2];
+    assert!(
+        buffer.len() >= 48 + count * channels * 4,
+        "{path} is shorter than its resolution"
+    );
+    let data = (0..count).map(|i| float(48 + i * channels * 4)).collect();
+
+    (data, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes a float32 grid to a file of its own, since tests run in parallel
+    fn write_vol(test: &str, size: [i32; 3], channels: i32, values: &[f32]) -> String {
+        let mut bytes = b"VOL\x03".to_vec();
+        for int in [1, size[0], size[1], size[2], channels] {
+            bytes.extend(int.to_le_bytes());
+        }
+        for bound in [0.0f32, 0.0, 0.0, 1.0, 1.0, 1.0] {
+            bytes.extend(bound.to_le_bytes());
+        }
+        for value in values {
+            bytes.extend(value.to_le_bytes());
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("vol_parser_{test}_{}.vol", std::process::id()));
+        std::fs::write(&path, bytes).expect("Failed to write test .vol file");
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn reads_grid_in_x_then_y_then_z_order() {
+        let values: Vec<f32> = (0..12u8).map(f32::from).collect();
+        let path = write_vol("order", [3, 2, 2], 1, &values);
+        let (data, size) = parse_vol(&path);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(size, [3, 2, 2]);
+        assert_eq!(data, values);
+    }
+
+    #[test]
+    fn keeps_first_of_interleaved_channels() {
+        // Voxels of (density, 10 * density, 100 * density)
+        let values: Vec<f32> = (0..4u8)
+            .map(f32::from)
+            .flat_map(|density| [density, density * 10.0, density * 100.0])
+            .collect();
+        let path = write_vol("channels", [2, 2, 1], 3, &values);
+        let (data, size) = parse_vol(&path);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(size, [2, 2, 1]);
+        assert_eq!(data, [0.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "shorter than its resolution")]
+    fn rejects_truncated_grids() {
+        let path = write_vol("truncated", [2, 2, 2], 1, &[0.0; 7]);
+        parse_vol(&path);
+    }
+}
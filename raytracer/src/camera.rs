@@ -1,46 +1,152 @@
-use std::f32::consts::PI;
-
 const MAX_BOUNCES: u32 = 100;
+const MAX_PASSES: u32 = 1000; // Of the surfaces rays go straight through, which aren't bounces
 const TILE_SIZE: u32 = 16;
 
-use geometry::{AABB, Hittable, HittableType};
-use material::{LambertianBase, Material, MaterialType};
-use util::{Color, Interval, Point, Ray, Unnormalized, Vec3};
+use geometry::{AABB, Bounds, Hittable, HittableType};
+use light::{AreaLight, EnvironmentLight, Light, LightSample, LightType, SunLight};
+use material::{
+    Bsdf, HenyeyGreenstein, LambertianBase, Layers, LobeFlags, Material, MaterialType, Medium,
+    MediumEvent,
+};
+use util::{
+    Color, HitResult, Interval, Normalized, Point, Ray, Unnormalized, Vec3,
+    sampler::{Sampler, SamplerType},
+};
 
-use crate::progress::make_progress_bar;
+use crate::{
+    film::{Film, FilmTile},
+    filter::FilterType,
+    nesting::InteriorStack,
+    progress::make_progress_bar,
+    sampler::SamplerKind,
+};
 
 pub struct Camera {
     pub image_width: u32,
     pub image_height: u32,
     samples_per_pixel: u32,
     look_from: Point,
+    w: Vec3<Normalized>,
+    defocus_angle: f32,
+    focus_distance: f32,
+    autofocus: bool,
+    defocus_disk_u: Vec3,
+    defocus_disk_v: Vec3,
     materials: Vec<MaterialType>,
     default_material: MaterialType,
+    media: Vec<Medium>,
+    scene_medium: Option<Medium>,
+    scene_bounds: Option<Bounds>, // Of the objects, which the scene medium fills
+    area_light: Option<AreaLight>,
+    lights: Vec<LightType>,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
     pixel00_loc: Vec3,
-    total_pixels: u32,
-    use_background_gradient: bool,
+    filter: FilterType,
+    sampler: SamplerKind,
+    seed: u64,
+    rr_depth: u32,
+    max_diffuse_depth: u32,
+    max_glossy_depth: u32,
+    max_transmission_depth: u32,
+    max_volume_depth: u32,
+    background: Background,
     pub debug_aabb: bool,
 }
 
+pub enum Background {
+    Gradient,
+    Solid(Color),
+    Environment(EnvironmentLight),
+    Sky {
+        sky: EnvironmentLight,
+        sun: Option<SunLight>,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CameraSettings {
+    pub look_from: Point,
+    pub look_at: Point,
+    pub up: Vec3,
+    pub vfov: f32, // Vertical field of view in degrees
+    pub aspect_ratio: f32,
+    pub image_width: u32,
+    pub image_height: Option<u32>, // Derived from the aspect ratio when not set
+    pub samples_per_pixel: u32,
+    pub defocus_angle: f32, // Lens cone angle in degrees, 0 for a pinhole camera
+    pub focus_distance: Option<f32>, // Distance to look_at when not set
+    pub autofocus: bool,    // Focus on whatever is at the image center
+    pub filter: FilterType,
+    pub sampler: SamplerKind,
+    pub seed: Option<u64>, // Random when not set
+    pub rr_depth: u32,     // Bounces before russian roulette starts
+    pub max_diffuse_depth: u32,
+    pub max_glossy_depth: u32,
+    pub max_transmission_depth: u32,
+    pub max_volume_depth: u32, // Random walks under surfaces take hundreds of steps
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            look_from: Point::new(25.0, 20.0, -50.0),
+            look_at: Point::new(0.0, 0.0, 0.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 35.0,
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 1000,
+            image_height: None,
+            samples_per_pixel: 10,
+            defocus_angle: 0.0,
+            focus_distance: None,
+            autofocus: false,
+            filter: FilterType::default(),
+            sampler: SamplerKind::default(),
+            seed: None,
+            rr_depth: 3,
+            max_diffuse_depth: 8,
+            max_glossy_depth: 8,
+            max_transmission_depth: 16,
+            max_volume_depth: 256,
+        }
+    }
+}
+
 impl Camera {
     pub fn new(
-        aspect_ratio: f32,
-        image_width: u32,
-        samples_per_pixel: u32,
+        settings: &CameraSettings,
         materials: Vec<MaterialType>,
-        use_background_gradient: bool,
+        media: Vec<Medium>,
+        scene_medium: Option<Medium>,
+        background: Background,
         debug_aabb: bool,
     ) -> Self {
-        let image_height = (image_width as f32 / aspect_ratio) as u32;
-
-        let look_from = Point::new(25.0, 20.0, -50.0);
-        let look_at = Point::new(0.0, 0.0, 0.0);
-        let up = Vec3::new(0.0, 1.0, 0.0).normalize();
-        let fov = 35;
+        let CameraSettings {
+            look_from,
+            look_at,
+            up,
+            vfov,
+            aspect_ratio,
+            image_width,
+            image_height,
+            samples_per_pixel,
+            defocus_angle,
+            focus_distance,
+            autofocus,
+            filter,
+            sampler,
+            seed,
+            rr_depth,
+            max_diffuse_depth,
+            max_glossy_depth,
+            max_transmission_depth,
+            max_volume_depth,
+        } = *settings;
+        let image_height =
+            image_height.unwrap_or_else(|| ((image_width as f32 / aspect_ratio) as u32).max(1));
 
-        let theta = degrees_to_radians(fov);
+        let theta = vfov.to_radians();
         let h = f32::tan(theta / 2.0);
         let viewport_height = 2.0 * h;
         let viewport_width = viewport_height * image_width as f32 / image_height as f32;
@@ -50,6 +156,12 @@ impl Camera {
         let u = Vec3::cross(&up, &w).normalize();
         let v = Vec3::cross(&w, &u);
 
+        // Lens basis, scaled to a unit focus distance
+        let defocus_radius = f32::tan(defocus_angle.to_radians() / 2.0);
+        let defocus_disk_u = u * defocus_radius;
+        let defocus_disk_v = v * defocus_radius;
+        let focus_distance = focus_distance.unwrap_or_else(|| (look_at - look_from).length());
+
         // Calculate the vectors across the horizontal and down the vertical viewport edges.
         let viewport_u = u * viewport_width; // Vector across viewport horizontal edge
         let viewport_v = -v * viewport_height; // Vector down viewport vertical edge
@@ -62,14 +174,14 @@ impl Camera {
         let pixel_delta_v = viewport_v * 1.0 / image_height as f32;
         let pixel00_loc = viewport_upper_left + pixel_delta_u * 0.5 + pixel_delta_v * 0.5;
 
-        let total_pixels = image_width * image_height;
-
         let default_material = MaterialType::Lambertian(LambertianBase {
             name: "Default".to_owned(),
             albedo: Color::new(1.0, 0.0, 1.0),
             normal_texture: None,
             orm: Vec3::new(1.0, 1.0, 0.0),
             alpha: 1.0,
+            emission: None,
+            layers: Layers::default(),
         });
 
         Camera {
@@ -77,31 +189,75 @@ impl Camera {
             image_height,
             samples_per_pixel,
             look_from,
+            w,
+            defocus_angle,
+            focus_distance,
+            autofocus,
+            defocus_disk_u,
+            defocus_disk_v,
             materials,
             default_material,
+            media,
+            scene_medium,
+            scene_bounds: None,
+            area_light: None,
+            lights: vec![],
             pixel_delta_u,
             pixel_delta_v,
             pixel00_loc,
-            total_pixels,
-            use_background_gradient,
+            filter,
+            sampler,
+            seed: seed.unwrap_or_else(|| {
+                let seed = rand::random();
+                println!("Using seed {seed}");
+                seed
+            }),
+            rr_depth,
+            max_diffuse_depth,
+            max_glossy_depth,
+            max_transmission_depth,
+            max_volume_depth,
+            background,
             debug_aabb,
         }
     }
 
-    pub fn render(&self, objects: Vec<HittableType>) -> Vec<Color> {
+    pub fn render(&mut self, objects: Vec<HittableType>, lights: Vec<LightType>) -> Vec<Color> {
+        self.lights = lights;
+
         // Create top-level node with BVH
         let aabb = AABB::new(objects);
+        let Bounds { min, max } = aabb.get_bounds();
+        self.scene_bounds = Some(Bounds {
+            min: *min,
+            max: *max,
+        });
+        self.area_light = AreaLight::new(&aabb, |index| {
+            self.materials[index].average_emission().luminance()
+        });
 
-        let num_tiles =
-            (self.total_pixels as f32 / TILE_SIZE as f32 / TILE_SIZE as f32).ceil() as u32;
-        self.collect_tiles(num_tiles, &aabb)
-            .into_iter()
-            .flatten()
-            .collect()
+        if self.autofocus {
+            self.autofocus(&aabb);
+        }
+
+        let tiles_x = self.image_width.div_ceil(TILE_SIZE);
+        let tiles_y = self.image_height.div_ceil(TILE_SIZE);
+
+        // Tiles are merged in order so the result doesn't depend on scheduling
+        let mut film = Film::new(self.image_width, self.image_height);
+        for tile in self.collect_tiles(tiles_x * tiles_y, &aabb) {
+            film.merge(&tile);
+        }
+
+        let mut framebuffer = film.resolve();
+        if self.debug_aabb {
+            self.overlay_debug_aabb(&mut framebuffer, &aabb);
+        }
+        framebuffer
     }
 
     #[cfg(feature = "multithreading")]
-    fn collect_tiles(&self, num_tiles: u32, objects: &AABB) -> Vec<Vec<Vec3>> {
+    fn collect_tiles(&self, num_tiles: u32, objects: &AABB) -> Vec<FilmTile> {
         use indicatif::ParallelProgressIterator;
         use rayon::prelude::*;
 
@@ -113,7 +269,7 @@ impl Camera {
     }
 
     #[cfg(not(feature = "multithreading"))]
-    fn collect_tiles(&self, num_tiles: u32, objects: &AABB) -> Vec<Vec<Vec3>> {
+    fn collect_tiles(&self, num_tiles: u32, objects: &AABB) -> Vec<FilmTile> {
         use indicatif::ProgressIterator;
         (0..num_tiles)
             .progress_with(make_progress_bar(num_tiles as u64))
@@ -121,86 +277,687 @@ impl Camera {
             .collect()
     }
 
-    fn render_tile(&self, tile_index: u32, objects: &AABB) -> Vec<Vec3> {
-        let mut tile_buffer = vec![];
-        let start_pixel = tile_index * TILE_SIZE * TILE_SIZE;
-        let end_pixel = ((tile_index + 1) * TILE_SIZE * TILE_SIZE).min(self.total_pixels);
-        for pixel_index in start_pixel..end_pixel {
-            let i = pixel_index % self.image_width;
-            let j = pixel_index / self.image_width;
+    fn render_tile(&self, tile_index: u32, objects: &AABB) -> FilmTile {
+        let tiles_x = self.image_width.div_ceil(TILE_SIZE);
+        let x0 = (tile_index % tiles_x) * TILE_SIZE;
+        let y0 = (tile_index / tiles_x) * TILE_SIZE;
+        let x1 = (x0 + TILE_SIZE).min(self.image_width);
+        let y1 = (y0 + TILE_SIZE).min(self.image_height);
 
-            let pixel_center =
-                self.pixel00_loc + self.pixel_delta_u * i as f32 + self.pixel_delta_v * j as f32;
+        let film_size = (self.image_width, self.image_height);
+        let mut tile = FilmTile::new(x0, y0, x1, y1, self.filter, film_size);
+        let mut sampler = self.sampler.create(self.samples_per_pixel, self.seed);
+        for j in y0..y1 {
+            for i in x0..x1 {
+                for sample_index in 0..self.samples_per_pixel {
+                    sampler.start_pixel_sample((i, j), sample_index);
 
-            let ray_dir = (pixel_center - self.look_from).normalize();
-            let mut color = Color::zero();
-            for _ in 0..self.samples_per_pixel {
-                let ray = Ray::new(self.look_from, ray_dir);
-                color = color + self.ray_color(ray, objects);
-            }
-            let mut color = color / self.samples_per_pixel as f32;
-
-            if self.debug_aabb {
-                const AABB_ALPHA: f32 = 0.50;
-                const MAX_COUNT: f32 = 1000.0;
-                let interval = Interval {
-                    min: 0.00001,
-                    max: f32::INFINITY,
-                };
+                    // Jitter the sample over the pixel's footprint
+                    let (dx, dy) = sampler.get_2d();
+                    let (px, py) = (i as f32 + dx, j as f32 + dy);
 
-                let debug_ray = Ray::new(self.look_from, ray_dir);
-                let count = objects.debug_hit_count(&debug_ray, &interval);
-                if count > 0 {
-                    let t = (count as f32 / MAX_COUNT).min(1.0);
-                    // cyan (few nodes) → red (many nodes)
-                    let aabb_color = Color::new(t, 1.0 - t, 1.0 - t);
-                    color = color * (1.0 - AABB_ALPHA) + aabb_color * AABB_ALPHA;
+                    let ray = self.get_ray(px, py, &mut sampler);
+                    let color = self.ray_color(ray, objects, &mut sampler);
+                    tile.add_sample(px, py, color, self.filter);
                 }
             }
+        }
+        tile
+    }
 
-            tile_buffer.push(color);
+    fn overlay_debug_aabb(&self, framebuffer: &mut [Color], objects: &AABB) {
+        const AABB_ALPHA: f32 = 0.50;
+        const MAX_COUNT: f32 = 1000.0;
+        let interval = Interval {
+            min: 0.00001,
+            max: f32::INFINITY,
+        };
+
+        for (pixel_index, color) in framebuffer.iter_mut().enumerate() {
+            let i = pixel_index as u32 % self.image_width;
+            let j = pixel_index as u32 / self.image_width;
+
+            let pixel_center =
+                self.pixel00_loc + self.pixel_delta_u * i as f32 + self.pixel_delta_v * j as f32;
+            let debug_ray = Ray::new(self.look_from, (pixel_center - self.look_from).normalize());
+            let count = objects.debug_hit_count(&debug_ray, &interval);
+            if count > 0 {
+                let t = (count as f32 / MAX_COUNT).min(1.0);
+                // cyan (few nodes) → red (many nodes)
+                let aabb_color = Color::new(t, 1.0 - t, 1.0 - t);
+                *color = *color * (1.0 - AABB_ALPHA) + aabb_color * AABB_ALPHA;
+            }
         }
-        tile_buffer
     }
 
-    fn ray_color(&self, mut ray: Ray, objects: &AABB) -> Vec3<Unnormalized> {
+    // Shoots a ray from a random point on the lens through the film position (px, py)
+    fn get_ray(&self, px: f32, py: f32, sampler: &mut SamplerType) -> Ray {
+        let pixel_sample =
+            self.pixel00_loc + self.pixel_delta_u * (px - 0.5) + self.pixel_delta_v * (py - 0.5);
+        let focus_point = self.look_from + (pixel_sample - self.look_from) * self.focus_distance;
+        let origin = if self.defocus_angle > 0.0 {
+            let p = Vec3::sample_in_unit_disk(sampler.get_2d());
+            self.look_from
+                + (self.defocus_disk_u * p.x + self.defocus_disk_v * p.y) * self.focus_distance
+        } else {
+            self.look_from
+        };
+
+        Ray::new(origin, (focus_point - origin).normalize())
+    }
+
+    // Focuses on the first surface hit through the center of the image
+    fn autofocus(&mut self, objects: &AABB) {
+        let interval = Interval {
+            min: 0.00001,
+            max: f32::INFINITY,
+        };
+
+        let ray = Ray::new(self.look_from, -self.w);
+        match objects.hit(&ray, &interval) {
+            Some(hit) => {
+                self.focus_distance = hit.t;
+                println!("Autofocus distance: {}", hit.t);
+            }
+            None => println!("Autofocus missed the scene, keeping focus distance"),
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn ray_color(
+        &self,
+        mut ray: Ray,
+        objects: &AABB,
+        sampler: &mut SamplerType,
+    ) -> Vec3<Unnormalized> {
         let mut depth = 0;
+        let mut passes = 0;
+        let (mut diffuse_depth, mut glossy_depth, mut transmission_depth) = (0, 0, 0);
+        let mut volume_depth = 0;
+        let mut radiance = Color::zero();
         let mut attenuation = Color::new(1.0, 1.0, 1.0);
-        while depth < MAX_BOUNCES {
+        let mut interiors = InteriorStack::default();
+        // Density the previous bounce picked the ray's direction with, None for camera rays and specular bounces
+        let mut scatter_pdf = None;
+        // Distance from that bounce, which grows as the ray passes through surfaces
+        let mut scatter_distance = 0.0;
+        // Ray cone around the path, a pixel wide, which sets how much textures are filtered
+        let cone_spread = self.pixel_delta_v.length();
+        let mut cone_width = 0.0;
+        while depth < MAX_BOUNCES && passes < MAX_PASSES {
             let interval = Interval {
                 min: 0.00001,
                 max: f32::INFINITY,
             };
+            let hit = objects.hit(&ray, &interval);
+
+            // The medium the ray travels through may scatter it before it reaches the surface
+            let t_max = hit.as_ref().map_or(f32::INFINITY, |hit| hit.t);
+            let mut scattered = None;
+            if let Some((medium, t_min, t_max)) = self.medium_segment(&interiors, &ray, t_max) {
+                match medium.sample(&ray, t_min, t_max, attenuation, sampler) {
+                    MediumEvent::Absorbed => break,
+                    MediumEvent::Pass { weight } => attenuation = attenuation * weight,
+                    MediumEvent::Scatter { t, weight } => {
+                        // Interiors absorb on top of whatever medium fills them
+                        attenuation = attenuation * weight * interiors.transmittance(t);
+                        scattered = Some((t, medium.phase));
+                    }
+                }
+            }
+
+            if let Some((t, phase)) = scattered {
+                let point = ray.at(t);
+                cone_width += cone_spread * t;
+
+                // Media have their own limit instead of the total one, random walks being long
+                volume_depth += 1;
+                if volume_depth > self.max_volume_depth {
+                    break;
+                }
+
+                // Shadow rays from inside a surface that scatters light would be blocked by it,
+                // so lights are left to be found by scattering alone
+                let enclosed = interiors
+                    .inside()
+                    .is_some_and(|(index, _)| !self.materials[index].is_interface());
+                let vertex = Vertex::Medium { point, phase };
+                let wo = -ray.dir;
+                if self.light_count() > 0 && !enclosed {
+                    let direct = self.sample_light(&vertex, &wo, objects, &interiors, sampler);
+                    radiance = radiance + attenuation * direct;
+                }
+
+                // Sampled exactly in proportion to the phase function, so the weight is one
+                let (wi, pdf) = phase.sample(&wo, sampler.get_2d());
+                scatter_pdf = (!enclosed).then_some(pdf);
+                scatter_distance = 0.0;
+                ray = vertex.spawn_ray(wi);
+            } else {
+                let Some(mut hit) = hit else {
+                    return radiance + attenuation * self.escaped_radiance(&ray, scatter_pdf);
+                };
+                cone_width += cone_spread * hit.t;
+                hit.footprint = footprint(&hit, &ray.dir, cone_width);
+                attenuation = attenuation * interiors.transmittance(hit.t);
+
+                // Medium boundaries and surfaces inside higher priority interiors are passed through
+                let material = self.material(hit.material_index);
+                let interior = hit.material_index.zip(material.interior());
+                if let Some((index, interior)) = interior
+                    && (material.is_interface() || interiors.is_hidden(&hit, &interior))
+                {
+                    interiors.cross(&hit, index, interior);
+                    ray = spawn_ray(&hit, ray.dir);
+                    scatter_distance += hit.t;
+                    passes += 1;
+                    continue;
+                }
+
+                let emitted = material.emitted(&hit);
+                if emitted.length_squared() > 0.0 {
+                    let distance = scatter_distance + hit.t;
+                    let weight = self.emission_weight(&ray, &hit, distance, scatter_pdf);
+                    radiance = radiance + attenuation * emitted * weight;
+                }
+
+                let bsdf = match interior {
+                    Some(_) => material.nested_bsdf(&ray, &hit, interiors.outside_ior(&hit)),
+                    None => material.bsdf(&ray, &hit),
+                };
+                let Some(bsdf) = bsdf else {
+                    break;
+                };
+                let wo = -ray.dir;
 
-            if let Some(hit) = objects.hit(&ray, &interval) {
-                let material = match hit.material_index {
-                    Some(mat_index) => &self.materials[mat_index],
-                    None => &self.default_material,
+                if self.light_count() > 0 && bsdf.flags().has_non_specular() {
+                    let vertex = Vertex::Surface {
+                        hit: &hit,
+                        bsdf: &bsdf,
+                    };
+                    let direct = self.sample_light(&vertex, &wo, objects, &interiors, sampler);
+                    radiance = radiance + attenuation * direct;
+                }
+
+                let Some(sample) = bsdf.sample(&wo, sampler.get_1d(), sampler.get_2d()) else {
+                    break;
+                };
+
+                // Each kind of bounce has its own limit, while passing through isn't a bounce
+                let pass = sample.flags.contains(LobeFlags::PASS);
+                let (lobe_depth, max_lobe_depth) = if sample.flags.contains(LobeFlags::TRANSMISSION)
+                {
+                    (&mut transmission_depth, self.max_transmission_depth)
+                } else if sample.flags.contains(LobeFlags::DIFFUSE) {
+                    (&mut diffuse_depth, self.max_diffuse_depth)
+                } else {
+                    (&mut glossy_depth, self.max_glossy_depth)
                 };
-                if matches!(material, MaterialType::Emissive(_)) {
-                    return attenuation * material.scatter(&ray, &hit).1;
+                if !pass {
+                    *lobe_depth += 1;
+                    if *lobe_depth > max_lobe_depth {
+                        break;
+                    }
                 }
 
-                let (scattered_ray, new_color) = material.scatter(&ray, &hit);
-                attenuation = attenuation * new_color;
-                ray = scattered_ray;
+                attenuation =
+                    attenuation * sample.f * (bsdf.abs_cos_theta(&sample.wi) / sample.pdf);
+                scatter_pdf = (!sample.flags.is_specular()).then_some(sample.pdf);
+                scatter_distance = 0.0;
+                if let Some((index, interior)) = interior
+                    && sample.flags.contains(LobeFlags::TRANSMISSION)
+                {
+                    interiors.cross(&hit, index, interior);
+                }
+                ray = spawn_ray(&hit, sample.wi);
+                if pass {
+                    passes += 1;
+                    continue;
+                }
                 depth += 1;
-            } else {
-                // Background gradient
-                if self.use_background_gradient {
-                    let t = 0.5 * (ray.dir.y + 1.0);
-                    return attenuation
-                        * (Color::new(1.0 - t, 1.0 - t, 1.0 - t) + Color::new(0.5, 0.7, 1.0) * t);
+            }
+
+            // Randomly end paths that carry little energy, boosting the survivors to stay unbiased
+            if depth >= self.rr_depth {
+                let survive_prob = attenuation.x.max(attenuation.y).max(attenuation.z).min(1.0);
+                if sampler.get_1d() >= survive_prob {
+                    break;
                 }
+                attenuation = attenuation / survive_prob;
+            }
+        }
+
+        radiance
+    }
+
+    // Next event estimation: connects the vertex to one light picked uniformly, with all
+    // emissive geometry counting as a single light and the environment and sun as one each
+    fn sample_light(
+        &self,
+        vertex: &Vertex,
+        wo: &Vec3<Normalized>,
+        objects: &AABB,
+        interiors: &InteriorStack,
+        sampler: &mut SamplerType,
+    ) -> Color {
+        let light_count = self.light_count();
+        let u_select = sampler.get_1d() * light_count as f32;
+        let index = (u_select as usize).min(light_count - 1);
+        let u = sampler.get_2d();
+
+        if let Some(light) = self.lights.get(index) {
+            let direct = self.sample_punctual_light(light, vertex, wo, objects, interiors, sampler);
+            return direct * light_count as f32;
+        }
+
+        // The area light's density already includes the chance of picking it
+        if let (Some(area_light), 0) = (&self.area_light, index - self.lights.len()) {
+            let u_select = u_select - index as f32;
+            return self.sample_area_light(
+                area_light, vertex, wo, objects, interiors, sampler, u_select, u,
+            );
+        }
+
+        let index = index - self.lights.len() - usize::from(self.area_light.is_some());
+        let sample = if let (Some(environment), 0) = (self.environment(), index) {
+            environment.sample(u)
+        } else {
+            Some(self.sun().expect("Light index out of range").sample(u))
+        };
+
+        let Some((light_sample, light_pdf)) = sample else {
+            return Color::zero();
+        };
+        self.sample_infinite_light(
+            &light_sample,
+            light_pdf,
+            vertex,
+            wo,
+            objects,
+            interiors,
+            sampler,
+        )
+    }
 
+    fn sample_punctual_light(
+        &self,
+        light: &LightType,
+        vertex: &Vertex,
+        wo: &Vec3<Normalized>,
+        objects: &AABB,
+        interiors: &InteriorStack,
+        sampler: &mut SamplerType,
+    ) -> Color {
+        let Some(light_sample) = light.sample_li(&vertex.point()) else {
+            return Color::zero();
+        };
+
+        let f = vertex.f(wo, &light_sample.wi);
+        if f.length_squared() == 0.0 {
+            return Color::zero();
+        }
+
+        let transmittance = self.transmittance_to(
+            vertex,
+            wo,
+            light_sample.wi,
+            light_sample.distance,
+            objects,
+            interiors,
+            sampler,
+        );
+
+        // Delta lights can't be hit by scattered rays, so there's nothing to weight against
+        f * light_sample.radiance * transmittance
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn sample_area_light(
+        &self,
+        area_light: &AreaLight,
+        vertex: &Vertex,
+        wo: &Vec3<Normalized>,
+        objects: &AABB,
+        interiors: &InteriorStack,
+        sampler: &mut SamplerType,
+        u_select: f32,
+        u: (f32, f32),
+    ) -> Color {
+        let light_hit = area_light.sample(u_select, u);
+
+        let to_light = light_hit.point - vertex.point();
+        let distance_squared = to_light.length_squared();
+        let wi = to_light.normalize();
+        let cos_light = wi.dot(&light_hit.normal).abs();
+        if distance_squared < 1e-8 || cos_light < 1e-6 {
+            return Color::zero();
+        }
+
+        let scatter_pdf = vertex.pdf(wo, &wi);
+        if scatter_pdf <= 0.0 {
+            return Color::zero();
+        }
+
+        let distance = distance_squared.sqrt();
+        let transmittance =
+            self.transmittance_to(vertex, wo, wi, distance, objects, interiors, sampler);
+        if transmittance.length_squared() == 0.0 {
+            return Color::zero();
+        }
+
+        // Convert the area density to solid angle at the shading point
+        let material = self.material(light_hit.material_index);
+        let light_pdf = self.area_light_pdf(area_light, material, distance_squared, cos_light);
+        let emitted = material.emitted(&light_hit);
+        let f = vertex.f(wo, &wi);
+        f * emitted * transmittance * (power_heuristic(light_pdf, scatter_pdf) / light_pdf)
+    }
+
+    // Lights infinitely far away that scattered rays can also hit, so both are MIS weighted
+    #[allow(clippy::too_many_arguments)]
+    fn sample_infinite_light(
+        &self,
+        light_sample: &LightSample,
+        light_pdf: f32,
+        vertex: &Vertex,
+        wo: &Vec3<Normalized>,
+        objects: &AABB,
+        interiors: &InteriorStack,
+        sampler: &mut SamplerType,
+    ) -> Color {
+        let scatter_pdf = vertex.pdf(wo, &light_sample.wi);
+        if scatter_pdf <= 0.0 {
+            return Color::zero();
+        }
+
+        let transmittance = self.transmittance_to(
+            vertex,
+            wo,
+            light_sample.wi,
+            f32::INFINITY,
+            objects,
+            interiors,
+            sampler,
+        );
+        if transmittance.length_squared() == 0.0 {
+            return Color::zero();
+        }
+
+        let light_pdf = light_pdf / self.light_count() as f32;
+        let f = vertex.f(wo, &light_sample.wi);
+        f * light_sample.radiance
+            * transmittance
+            * (power_heuristic(light_pdf, scatter_pdf) / light_pdf)
+    }
+
+    // Light left along a shadow ray, which passes through medium boundaries and hidden surfaces
+    // but is blocked by anything else
+    #[allow(clippy::too_many_arguments)]
+    fn transmittance_to(
+        &self,
+        vertex: &Vertex,
+        wo: &Vec3<Normalized>,
+        wi: Vec3<Normalized>,
+        distance: f32,
+        objects: &AABB,
+        interiors: &InteriorStack,
+        sampler: &mut SamplerType,
+    ) -> Color {
+        // Leaving through the other side of a transmissive surface enters or leaves its interior.
+        // Triangle normals face the incoming ray while sphere normals face out, so compare sides
+        let mut interiors = interiors.clone();
+        if let Vertex::Surface { hit, .. } = vertex
+            && let Some(index) = hit.material_index
+            && let Some(interior) = self.materials[index].interior()
+            && wi.dot(&hit.normal) * wo.dot(&hit.normal) < 0.0
+        {
+            interiors.cross(hit, index, interior);
+        }
+        let mut ray = vertex.spawn_ray(wi);
+        let mut remaining = distance * 0.999;
+        let mut transmittance = Color::new(1.0, 1.0, 1.0);
+        loop {
+            let shadow_interval = Interval {
+                min: 0.00001,
+                max: remaining,
+            };
+            let hit = objects.hit(&ray, &shadow_interval);
+
+            let t_max = hit.as_ref().map_or(remaining, |hit| hit.t);
+            if let Some((medium, t_min, t_max)) = self.medium_segment(&interiors, &ray, t_max) {
+                transmittance = transmittance * medium.transmittance(&ray, t_min, t_max, sampler);
+            }
+            // Absorbing interiors are closed, so rays leaving the scene never start inside one
+            if t_max.is_finite() {
+                transmittance = transmittance * interiors.transmittance(t_max);
+            }
+
+            let Some(hit) = hit else {
+                return transmittance;
+            };
+            let material = self.material(hit.material_index);
+            let Some((index, interior)) =
+                hit.material_index
+                    .zip(material.interior())
+                    .filter(|(_, interior)| {
+                        material.is_interface() || interiors.is_hidden(&hit, interior)
+                    })
+            else {
                 return Color::zero();
+            };
+            if transmittance.x.max(transmittance.y).max(transmittance.z) <= 0.0 {
+                return Color::zero();
+            }
+
+            interiors.cross(&hit, index, interior);
+            ray = spawn_ray(&hit, ray.dir);
+            remaining -= hit.t;
+        }
+    }
+
+    // The medium the ray is in, with the part of the ray up to t_max it fills
+    fn medium_segment(
+        &self,
+        interiors: &InteriorStack,
+        ray: &Ray,
+        t_max: f32,
+    ) -> Option<(&Medium, f32, f32)> {
+        // Interiors displace the scene medium, even those without a medium of their own
+        if let Some((_, interior)) = interiors.inside() {
+            return interior
+                .medium
+                .map(|index| (&self.media[index], 0.0, t_max));
+        }
+
+        let medium = self.scene_medium.as_ref()?;
+        let Bounds { min, max } = self.scene_bounds.as_ref()?;
+        let inside = ray.box_interval(min, max, &Interval::new(0.0, t_max))?;
+        Some((medium, inside.min, inside.max))
+    }
+
+    // MIS weight of emission found by scattering, which light sampling could also have found.
+    // distance is measured from the vertex that sampled the direction, like light sampling does
+    fn emission_weight(
+        &self,
+        ray: &Ray,
+        hit: &HitResult,
+        distance: f32,
+        scatter_pdf: Option<f32>,
+    ) -> f32 {
+        match (&self.area_light, scatter_pdf) {
+            (Some(area_light), Some(scatter_pdf)) => {
+                let cos_light = ray.dir.dot(&hit.normal).abs();
+                let material = self.material(hit.material_index);
+                let distance_squared = distance * distance;
+                let light_pdf =
+                    self.area_light_pdf(area_light, material, distance_squared, cos_light);
+                power_heuristic(scatter_pdf, light_pdf)
+            }
+            _ => 1.0,
+        }
+    }
+
+    // Solid angle density of sample_light() picking a point on an emitter made of material
+    fn area_light_pdf(
+        &self,
+        area_light: &AreaLight,
+        material: &MaterialType,
+        distance_squared: f32,
+        cos_light: f32,
+    ) -> f32 {
+        let pdf_area = area_light.pdf_area(material.average_emission().luminance());
+        pdf_area * distance_squared / (cos_light * self.light_count() as f32)
+    }
+
+    // Background seen by rays leaving the scene, weighted like emission_weight() against light
+    // sampling of the environment and sun
+    fn escaped_radiance(&self, ray: &Ray, scatter_pdf: Option<f32>) -> Color {
+        let environment_pdf = self
+            .environment()
+            .map(|environment| environment.pdf(&ray.dir));
+        let mut color =
+            self.background_color(ray) * self.infinite_light_weight(scatter_pdf, environment_pdf);
+
+        if let Some(sun) = self.sun() {
+            let weight = self.infinite_light_weight(scatter_pdf, Some(sun.pdf(&ray.dir)));
+            color = color + sun.radiance(&ray.dir) * weight;
+        }
+        color
+    }
+
+    fn infinite_light_weight(&self, scatter_pdf: Option<f32>, light_pdf: Option<f32>) -> f32 {
+        match (scatter_pdf, light_pdf) {
+            (Some(scatter_pdf), Some(light_pdf)) => {
+                power_heuristic(scatter_pdf, light_pdf / self.light_count() as f32)
+            }
+            _ => 1.0,
+        }
+    }
+
+    fn light_count(&self) -> usize {
+        self.lights.len()
+            + usize::from(self.area_light.is_some())
+            + usize::from(self.environment().is_some())
+            + usize::from(self.sun().is_some())
+    }
+
+    fn environment(&self) -> Option<&EnvironmentLight> {
+        match &self.background {
+            Background::Environment(environment)
+            | Background::Sky {
+                sky: environment, ..
+            } => Some(environment),
+            _ => None,
+        }
+    }
+
+    fn sun(&self) -> Option<&SunLight> {
+        match &self.background {
+            Background::Sky { sun, .. } => sun.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn material(&self, material_index: Option<usize>) -> &MaterialType {
+        match material_index {
+            Some(mat_index) => &self.materials[mat_index],
+            None => &self.default_material,
+        }
+    }
+
+    fn background_color(&self, ray: &Ray) -> Color {
+        match self.background {
+            Background::Gradient => {
+                let t = 0.5 * (ray.dir.y + 1.0);
+                Color::new(1.0 - t, 1.0 - t, 1.0 - t) + Color::new(0.5, 0.7, 1.0) * t
             }
+            Background::Solid(color) => color,
+            Background::Environment(ref environment)
+            | Background::Sky {
+                sky: ref environment,
+                ..
+            } => environment.radiance(&ray.dir),
         }
+    }
+}
+
+// Where a path scatters, on a surface or inside a medium
+enum Vertex<'a> {
+    Surface {
+        hit: &'a HitResult,
+        bsdf: &'a Bsdf,
+    },
+    Medium {
+        point: Point,
+        phase: HenyeyGreenstein,
+    },
+}
 
-        attenuation
+impl Vertex<'_> {
+    fn point(&self) -> Point {
+        match self {
+            Vertex::Surface { hit, .. } => hit.point,
+            Vertex::Medium { point, .. } => *point,
+        }
+    }
+
+    // Light scattered from wi to wo, including the cosine at surfaces
+    fn f(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> Color {
+        match self {
+            Vertex::Surface { bsdf, .. } => bsdf.eval(wo, wi) * bsdf.abs_cos_theta(wi),
+            Vertex::Medium { phase, .. } => {
+                let p = phase.p(wo, wi);
+                Color::new(p, p, p)
+            }
+        }
     }
+
+    fn pdf(&self, wo: &Vec3<Normalized>, wi: &Vec3<Normalized>) -> f32 {
+        match self {
+            Vertex::Surface { bsdf, .. } => bsdf.pdf(wo, wi),
+            Vertex::Medium { phase, .. } => phase.p(wo, wi),
+        }
+    }
+
+    fn spawn_ray(&self, dir: Vec3<Normalized>) -> Ray {
+        match self {
+            Vertex::Surface { hit, .. } => spawn_ray(hit, dir),
+            Vertex::Medium { point, .. } => Ray::new(*point, dir),
+        }
+    }
+}
+
+// Offsets the origin to the side of the surface the ray leaves from to avoid hitting it again
+fn spawn_ray(hit: &HitResult, dir: Vec3<Normalized>) -> Ray {
+    let offset = (hit.t * 1e-4).max(1e-4);
+    let normal = if dir.dot(&hit.normal) < 0.0 {
+        -hit.normal
+    } else {
+        hit.normal
+    };
+    Ray::new(hit.point + normal * offset, dir)
+}
+
+// Half axes of the ellipse a ray cone of the given width covers where it meets the surface,
+// stretched along the ray's direction the more grazing it comes in
+fn footprint(hit: &HitResult, dir: &Vec3<Normalized>, width: f32) -> (Vec3, Vec3) {
+    let cos = hit.normal.dot(dir);
+    let along = *dir - hit.normal * cos;
+    let major = if along.length_squared() > 1e-8 {
+        along.normalize()
+    } else if hit.normal.x.abs() < 0.9 {
+        Vec3::cross(&hit.normal, &Vec3::<Normalized>::new(1.0, 0.0, 0.0)).normalize()
+    } else {
+        Vec3::cross(&hit.normal, &Vec3::<Normalized>::new(0.0, 1.0, 0.0)).normalize()
+    };
+    let minor = Vec3::cross(&hit.normal, &major);
+    (
+        major * (0.5 * width / cos.abs().max(0.05)),
+        minor * (0.5 * width),
+    )
 }
 
-fn degrees_to_radians(fov: u8) -> f32 {
-    f32::from(fov) / 180.0 * PI
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a.is_infinite() { 1.0 } else { a / (a + b) }
 }
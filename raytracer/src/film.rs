@@ -0,0 +1,112 @@
+#![allow(clippy::cast_possible_wrap)]
+
+use util::Color;
+
+use crate::filter::{Filter, FilterType};
+
+// Accumulates filter-weighted samples for a rectangle of pixels
+pub struct FilmTile {
+    x0: i32,
+    y0: i32,
+    x1: i32, // Exclusive
+    y1: i32, // Exclusive
+    sums: Vec<Color>,
+    weights: Vec<f32>,
+}
+
+impl FilmTile {
+    // The tile covers its pixels plus a margin for samples splatted across the tile border
+    pub fn new(
+        x0: u32,
+        y0: u32,
+        x1: u32,
+        y1: u32,
+        filter: FilterType,
+        film_size: (u32, u32),
+    ) -> Self {
+        let (width, height) = film_size;
+        let margin = filter.radius().ceil() as i32;
+        let x0 = (x0 as i32 - margin).max(0);
+        let y0 = (y0 as i32 - margin).max(0);
+        let x1 = (x1 as i32 + margin).min(width as i32);
+        let y1 = (y1 as i32 + margin).min(height as i32);
+
+        let len = ((x1 - x0) * (y1 - y0)) as usize;
+        Self {
+            x0,
+            y0,
+            x1,
+            y1,
+            sums: vec![Color::zero(); len],
+            weights: vec![0.0; len],
+        }
+    }
+
+    // (px, py) is the sample position in continuous pixel coordinates
+    pub fn add_sample(&mut self, px: f32, py: f32, color: Color, filter: FilterType) {
+        let radius = filter.radius();
+
+        // Discrete pixel (i, j) has its center at (i + 0.5, j + 0.5)
+        let min_x = ((px - 0.5 - radius).ceil() as i32).max(self.x0);
+        let max_x = ((px - 0.5 + radius).floor() as i32).min(self.x1 - 1);
+        let min_y = ((py - 0.5 - radius).ceil() as i32).max(self.y0);
+        let max_y = ((py - 0.5 + radius).floor() as i32).min(self.y1 - 1);
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let weight = filter.evaluate(px - (x as f32 + 0.5), py - (y as f32 + 0.5));
+                if weight == 0.0 {
+                    continue;
+                }
+
+                let index = ((y - self.y0) * (self.x1 - self.x0) + (x - self.x0)) as usize;
+                self.sums[index] = self.sums[index] + color * weight;
+                self.weights[index] += weight;
+            }
+        }
+    }
+}
+
+pub struct Film {
+    width: u32,
+    sums: Vec<Color>,
+    weights: Vec<f32>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        Self {
+            width,
+            sums: vec![Color::zero(); len],
+            weights: vec![0.0; len],
+        }
+    }
+
+    pub fn merge(&mut self, tile: &FilmTile) {
+        let tile_width = (tile.x1 - tile.x0) as usize;
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let tile_index = (y - tile.y0) as usize * tile_width + (x - tile.x0) as usize;
+                let index = y as usize * self.width as usize + x as usize;
+                self.sums[index] = self.sums[index] + tile.sums[tile_index];
+                self.weights[index] += tile.weights[tile_index];
+            }
+        }
+    }
+
+    // Normalizes the weighted sums into final pixel colors
+    pub fn resolve(self) -> Vec<Color> {
+        self.sums
+            .into_iter()
+            .zip(self.weights)
+            .map(|(sum, weight)| {
+                if weight == 0.0 {
+                    Color::zero()
+                } else {
+                    sum / weight
+                }
+            })
+            .collect()
+    }
+}
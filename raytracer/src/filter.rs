@@ -0,0 +1,94 @@
+use std::f32::consts::PI;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+const GAUSSIAN_SIGMA: f32 = 0.5;
+const MITCHELL_B: f32 = 1.0 / 3.0;
+const MITCHELL_C: f32 = 1.0 / 3.0;
+
+pub trait Filter {
+    // Half-width of the filter's support in pixels
+    fn radius(&self) -> f32;
+    // Weight of a sample at an offset (x, y) from a pixel center
+    fn evaluate(&self, x: f32, y: f32) -> f32;
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterType {
+    Box,
+    Tent,
+    #[default]
+    Gaussian,
+    Mitchell,
+    BlackmanHarris,
+}
+
+impl Filter for FilterType {
+    fn radius(&self) -> f32 {
+        match self {
+            FilterType::Box => 0.5,
+            FilterType::Tent => 1.0,
+            FilterType::Gaussian => 1.5,
+            FilterType::Mitchell | FilterType::BlackmanHarris => 2.0,
+        }
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        let radius = self.radius();
+        if x.abs() > radius || y.abs() > radius {
+            return 0.0;
+        }
+
+        let separable = |f: fn(f32, f32) -> f32| f(x, radius) * f(y, radius);
+        match self {
+            FilterType::Box => 1.0,
+            FilterType::Tent => separable(tent),
+            FilterType::Gaussian => separable(gaussian),
+            FilterType::Mitchell => separable(mitchell),
+            FilterType::BlackmanHarris => separable(blackman_harris),
+        }
+    }
+}
+
+fn tent(x: f32, radius: f32) -> f32 {
+    (radius - x.abs()).max(0.0)
+}
+
+fn gaussian(x: f32, radius: f32) -> f32 {
+    let g = |x: f32| (-x * x / (2.0 * GAUSSIAN_SIGMA * GAUSSIAN_SIGMA)).exp();
+    // Shift down so the filter goes to zero at its radius
+    (g(x) - g(radius)).max(0.0)
+}
+
+fn mitchell(x: f32, radius: f32) -> f32 {
+    let (b, c) = (MITCHELL_B, MITCHELL_C);
+    // The Mitchell-Netravali polynomials are defined over [-2, 2]
+    let x = (2.0 * x / radius).abs();
+    if x <= 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b))
+            / 6.0
+    } else if x <= 2.0 {
+        ((-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+fn blackman_harris(x: f32, radius: f32) -> f32 {
+    const A0: f32 = 0.358_75;
+    const A1: f32 = 0.488_29;
+    const A2: f32 = 0.141_28;
+    const A3: f32 = 0.011_68;
+
+    // Map [-radius, radius] onto the window's [0, 1] domain
+    let t = (x + radius) / (2.0 * radius);
+    A0 - A1 * (2.0 * PI * t).cos() + A2 * (4.0 * PI * t).cos() - A3 * (6.0 * PI * t).cos()
+}
@@ -4,64 +4,246 @@
     clippy::cast_precision_loss
 )]
 
-use clap::Parser;
-use geometry::Hittable;
-use parser::parse_glb;
+use std::path::PathBuf;
+
+use clap::{Args as ClapArgs, Parser};
 use util::Vec3;
 
-use crate::camera::Camera;
+use crate::{
+    camera::{Camera, CameraSettings},
+    filter::FilterType,
+    output::ColorSpace,
+    post::{PostSettings, ToneMapper},
+    sampler::SamplerKind,
+    scene::Scene,
+};
 
 mod camera;
+mod film;
+mod filter;
+mod nesting;
+mod output;
+mod post;
 mod progress;
+mod sampler;
+mod scene;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    #[arg(short, long, default_value = "10")]
-    pub samples: u32,
+    /// Scene description file (JSON)
+    #[arg(default_value = "scenes/titanic.json")]
+    pub scene: PathBuf,
+    #[command(flatten)]
+    pub camera: CameraArgs,
+    #[command(flatten)]
+    pub post: PostArgs,
+    /// Color space of the output image, overriding the scene's
+    #[arg(long, value_enum)]
+    pub color_space: Option<ColorSpace>,
     #[arg(long, default_value = "false")]
     pub debug_aabb: bool,
 }
 
+// Overrides for the scene's camera settings
+#[derive(ClapArgs, Debug)]
+pub struct CameraArgs {
+    /// Camera position as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub look_from: Option<Vec3>,
+    /// Point the camera looks at as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub look_at: Option<Vec3>,
+    /// Camera up vector as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub up: Option<Vec3>,
+    /// Vertical field of view in degrees
+    #[arg(long)]
+    pub vfov: Option<f32>,
+    #[arg(long)]
+    pub aspect_ratio: Option<f32>,
+    #[arg(long)]
+    pub width: Option<u32>,
+    /// Derived from the width and aspect ratio when not set
+    #[arg(long)]
+    pub height: Option<u32>,
+    #[arg(short, long)]
+    pub samples: Option<u32>,
+    /// Lens cone angle in degrees, 0 disables depth of field
+    #[arg(long)]
+    pub defocus_angle: Option<f32>,
+    /// Distance to the plane in perfect focus
+    #[arg(long)]
+    pub focus_distance: Option<f32>,
+    /// Focus on the surface at the center of the image
+    #[arg(long)]
+    pub autofocus: bool,
+    /// Pixel reconstruction filter
+    #[arg(long, value_enum)]
+    pub filter: Option<FilterType>,
+    /// Sample generator for pixel, lens and bounce decisions
+    #[arg(long, value_enum)]
+    pub sampler: Option<SamplerKind>,
+    /// Seed for reproducible renders
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Bounces before paths can be ended by russian roulette
+    #[arg(long)]
+    pub rr_depth: Option<u32>,
+    /// Maximum number of diffuse bounces
+    #[arg(long)]
+    pub max_diffuse_depth: Option<u32>,
+    /// Maximum number of glossy and mirror bounces
+    #[arg(long)]
+    pub max_glossy_depth: Option<u32>,
+    /// Maximum number of bounces through transmissive surfaces
+    #[arg(long)]
+    pub max_transmission_depth: Option<u32>,
+    /// Maximum number of scattering events in media and subsurface random walks
+    #[arg(long)]
+    pub max_volume_depth: Option<u32>,
+}
+
+// Overrides for the scene's post-processing settings
+#[derive(ClapArgs, Debug)]
+pub struct PostArgs {
+    /// Exposure adjustment in stops
+    #[arg(long, allow_hyphen_values = true)]
+    pub exposure: Option<f32>,
+    /// Color temperature in Kelvin that comes out white
+    #[arg(long)]
+    pub white_balance: Option<f32>,
+    /// Curve compressing highlights into the displayable range
+    #[arg(long, value_enum)]
+    pub tone_mapper: Option<ToneMapper>,
+}
+
+impl PostArgs {
+    fn apply(&self, settings: &mut PostSettings) {
+        if let Some(exposure) = self.exposure {
+            settings.exposure = exposure;
+        }
+        if self.white_balance.is_some() {
+            settings.white_balance = self.white_balance;
+        }
+        if let Some(tone_mapper) = self.tone_mapper {
+            settings.tone_mapper = tone_mapper;
+        }
+    }
+}
+
+impl CameraArgs {
+    fn apply(&self, settings: &mut CameraSettings) {
+        if let Some(look_from) = self.look_from {
+            settings.look_from = look_from;
+        }
+        if let Some(look_at) = self.look_at {
+            settings.look_at = look_at;
+        }
+        if let Some(up) = self.up {
+            settings.up = up;
+        }
+        if let Some(vfov) = self.vfov {
+            settings.vfov = vfov;
+        }
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            settings.aspect_ratio = aspect_ratio;
+        }
+        if let Some(width) = self.width {
+            settings.image_width = width;
+        }
+        if self.height.is_some() {
+            settings.image_height = self.height;
+        }
+        if let Some(samples) = self.samples {
+            settings.samples_per_pixel = samples;
+        }
+        if let Some(defocus_angle) = self.defocus_angle {
+            settings.defocus_angle = defocus_angle;
+        }
+        if self.focus_distance.is_some() {
+            settings.focus_distance = self.focus_distance;
+        }
+        if self.autofocus {
+            settings.autofocus = true;
+        }
+        if let Some(filter) = self.filter {
+            settings.filter = filter;
+        }
+        if let Some(sampler) = self.sampler {
+            settings.sampler = sampler;
+        }
+        if self.seed.is_some() {
+            settings.seed = self.seed;
+        }
+        if let Some(rr_depth) = self.rr_depth {
+            settings.rr_depth = rr_depth;
+        }
+        if let Some(max_diffuse_depth) = self.max_diffuse_depth {
+            settings.max_diffuse_depth = max_diffuse_depth;
+        }
+        if let Some(max_glossy_depth) = self.max_glossy_depth {
+            settings.max_glossy_depth = max_glossy_depth;
+        }
+        if let Some(max_transmission_depth) = self.max_transmission_depth {
+            settings.max_transmission_depth = max_transmission_depth;
+        }
+        if let Some(max_volume_depth) = self.max_volume_depth {
+            settings.max_volume_depth = max_volume_depth;
+        }
+    }
+}
+
+fn parse_vec3(value: &str) -> Result<Vec3, String> {
+    let components = value
+        .split(',')
+        .map(|c| c.trim().parse::<f32>().map_err(|e| format!("{c:?}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!("Expected 3 comma separated values. Got: {value:?}")),
+    }
+}
+
 fn main() {
     let args = Args::parse();
 
-    let (mut objects, materials) = parse_glb("objs/Titanic/combined.glb", 0);
+    let scene = Scene::load(&args.scene);
+    let (objects, materials, mut lights, media) = scene.load_objects();
+    lights.extend(scene.load_lights());
 
-    objects[0].scale(&Vec3::new(0.25, 0.25, 0.25));
-    objects[0].translate(&Vec3::new(-30.0, -5.0, 0.0));
+    let mut settings = CameraSettings::default();
+    scene.camera.apply(&mut settings);
+    args.camera.apply(&mut settings);
 
-    let camera = Camera::new(
-        16.0 / 9.0,
-        1000,
-        args.samples,
+    let mut camera = Camera::new(
+        &settings,
         materials,
-        true,
+        media,
+        scene.load_scene_medium(),
+        scene.load_background(),
         args.debug_aabb,
     );
     println!("Rendering...");
 
     let start = std::time::Instant::now();
-    let framebuffer = camera.render(objects);
+    let mut framebuffer = camera.render(objects, lights);
     let duration = start.elapsed();
     println!("Render time: {duration:?}");
 
-    let file = "output.png";
-    image::save_buffer(
+    let mut post_settings = PostSettings::default();
+    scene.post_processing.apply(&mut post_settings);
+    args.post.apply(&mut post_settings);
+    post::apply(&post_settings, &mut framebuffer);
+
+    let file = &scene.output;
+    output::save(
         file,
-        &framebuffer
-            .iter()
-            .flat_map(|c| {
-                let ir = (255.999 * c.x.clamp(0.0, 0.999)) as u8;
-                let ig = (255.999 * c.y.clamp(0.0, 0.999)) as u8;
-                let ib = (255.999 * c.z.clamp(0.0, 0.999)) as u8;
-                [ir, ig, ib]
-            })
-            .collect::<Vec<u8>>(),
+        &framebuffer,
         camera.image_width,
         camera.image_height,
-        image::ColorType::Rgb8,
-    )
-    .unwrap();
-    println!("Saved to {file}");
+        args.color_space.unwrap_or(scene.color_space),
+    );
+    println!("Saved to {}", file.display());
 }
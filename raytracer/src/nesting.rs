@@ -0,0 +1,177 @@
+use material::Interior;
+use util::{Color, HitResult};
+
+// Interiors of the transmissive surfaces a path has entered, so objects can be nested in each
+// other, after "Simple Nested Dielectrics in Ray Traced Images" by Schmidt and Budge
+#[derive(Clone, Default)]
+pub struct InteriorStack {
+    entries: Vec<Entry>,
+}
+
+#[derive(Clone)]
+struct Entry {
+    object_id: u64, // Objects sharing a material are left separately
+    material_index: usize,
+    interior: Interior,
+}
+
+impl InteriorStack {
+    // Light remaining after travelling the distance through the space the path is in
+    pub fn transmittance(&self, distance: f32) -> Color {
+        self.current(None).map(|entry| &entry.interior).map_or(
+            Color::new(1.0, 1.0, 1.0),
+            |interior| {
+                let absorption = interior.absorption;
+                Color::new(
+                    (-absorption.x * distance).exp(),
+                    (-absorption.y * distance).exp(),
+                    (-absorption.z * distance).exp(),
+                )
+            },
+        )
+    }
+
+    // A surface inside a higher priority interior doesn't exist, paths pass straight through it
+    pub fn is_hidden(&self, hit: &HitResult, interior: &Interior) -> bool {
+        self.current(Some(hit.object_id))
+            .is_some_and(|current| current.interior.priority > interior.priority)
+    }
+
+    // The interior filling the space the path is in, with the material index of its surface
+    pub fn inside(&self) -> Option<(usize, &Interior)> {
+        self.current(None)
+            .map(|entry| (entry.material_index, &entry.interior))
+    }
+
+    // Index of refraction on the other side of the hit surface from its interior
+    pub fn outside_ior(&self, hit: &HitResult) -> f32 {
+        self.current(Some(hit.object_id))
+            .map_or(1.0, |entry| entry.interior.ior)
+    }
+
+    // Enters the interior of the hit object through its front face, leaves it through the back
+    pub fn cross(&mut self, hit: &HitResult, material_index: usize, interior: Interior) {
+        if hit.front_face {
+            self.entries.push(Entry {
+                object_id: hit.object_id,
+                material_index,
+                interior,
+            });
+        } else if let Some(position) = self.entries.iter().rposition(|entry| {
+            entry.object_id == hit.object_id && entry.material_index == material_index
+        }) {
+            self.entries.remove(position);
+        }
+    }
+
+    // The interior filling the space, the last entered of the highest priority
+    fn current(&self, excluding: Option<u64>) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .filter(|entry| Some(entry.object_id) != excluding)
+            .max_by_key(|entry| entry.interior.priority)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use util::{Point, TexCoord, Vec3};
+
+    use super::*;
+
+    fn hit(object_id: u64, front_face: bool) -> HitResult {
+        let tex_coord = TexCoord {
+            u: 0.0,
+            v: 0.0,
+            gradients: (Point::zero(), Point::zero()),
+        };
+        HitResult {
+            normal: Vec3::new(0.0, 1.0, 0.0).normalize(),
+            tangent: None,
+            t: 1.0,
+            point: Point::zero(),
+            material_index: Some(0),
+            tex_coords: [tex_coord; 2],
+            front_face,
+            object_id,
+            footprint: (Point::zero(), Point::zero()),
+        }
+    }
+
+    fn interior(ior: f32, absorption: f32, priority: u32) -> Interior {
+        Interior {
+            ior,
+            absorption: Color::new(absorption, absorption, absorption),
+            medium: None,
+            priority,
+        }
+    }
+
+    #[test]
+    fn empty_stack_is_vacuum() {
+        let stack = InteriorStack::default();
+        assert!(stack.inside().is_none());
+        assert!((stack.outside_ior(&hit(1, true)) - 1.0).abs() < 1e-6);
+        assert!((stack.transmittance(5.0).x - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn leaving_restores_the_outer_interior() {
+        let mut stack = InteriorStack::default();
+        stack.cross(&hit(1, true), 0, interior(1.5, 0.0, 0));
+        stack.cross(&hit(2, true), 1, interior(1.33, 0.5, 0));
+        assert_eq!(stack.inside().map(|(index, _)| index), Some(1));
+        assert!((stack.transmittance(2.0).x - (-1.0f32).exp()).abs() < 1e-6);
+
+        stack.cross(&hit(2, false), 1, interior(1.33, 0.5, 0));
+        assert_eq!(stack.inside().map(|(index, _)| index), Some(0));
+        stack.cross(&hit(1, false), 0, interior(1.5, 0.0, 0));
+        assert!(stack.inside().is_none());
+    }
+
+    #[test]
+    fn overlapping_objects_of_one_material_are_left_separately() {
+        let glass = interior(1.5, 0.0, 0);
+        let mut stack = InteriorStack::default();
+        stack.cross(&hit(1, true), 0, glass);
+        stack.cross(&hit(2, true), 0, glass);
+        stack.cross(&hit(1, false), 0, glass);
+        assert!(stack.inside().is_some());
+        stack.cross(&hit(2, false), 0, glass);
+        assert!(stack.inside().is_none());
+    }
+
+    #[test]
+    fn leaving_what_was_never_entered_changes_nothing() {
+        let mut stack = InteriorStack::default();
+        stack.cross(&hit(1, true), 0, interior(1.5, 0.0, 0));
+        stack.cross(&hit(2, false), 0, interior(1.5, 0.0, 0));
+        assert_eq!(stack.inside().map(|(index, _)| index), Some(0));
+    }
+
+    #[test]
+    fn higher_priority_interiors_hide_the_surfaces_inside_them() {
+        // A glass holding liquid whose surface overlaps the glass wall
+        let glass = interior(1.5, 0.0, 2);
+        let liquid = interior(1.33, 0.0, 1);
+        let mut stack = InteriorStack::default();
+        stack.cross(&hit(1, true), 0, glass);
+        assert!(stack.is_hidden(&hit(2, true), &liquid));
+        assert!(!stack.is_hidden(&hit(1, false), &glass));
+
+        // Once out of the wall the liquid surface counts, with the glass on its other side
+        stack.cross(&hit(2, true), 1, liquid);
+        stack.cross(&hit(1, false), 0, glass);
+        assert!(!stack.is_hidden(&hit(2, false), &liquid));
+        assert!((stack.outside_ior(&hit(2, false)) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn outside_ior_skips_the_hit_object() {
+        let mut stack = InteriorStack::default();
+        stack.cross(&hit(1, true), 0, interior(1.33, 0.0, 0));
+        stack.cross(&hit(2, true), 1, interior(1.5, 0.0, 0));
+        assert!((stack.outside_ior(&hit(2, false)) - 1.33).abs() < 1e-6);
+        assert!((stack.outside_ior(&hit(3, true)) - 1.5).abs() < 1e-6);
+    }
+}
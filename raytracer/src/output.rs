@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use image::{Rgb32FImage, RgbImage};
+use serde::Deserialize;
+use util::{
+    Color,
+    color::{linear_to_srgb, mat3_transform},
+};
+
+// Rec.709 primaries to ACES AP1, with the white point adapted from D65 to ACES' D60 by Bradford
+const REC709_TO_AP1: [[f32; 3]; 3] = [
+    [0.613_097, 0.339_523, 0.047_379],
+    [0.070_194, 0.916_354, 0.013_452],
+    [0.020_616, 0.109_570, 0.869_815],
+];
+
+// Color space of the written image. Rendering itself happens in linear light with the
+// primaries of sRGB, which all inputs are converted to
+#[derive(Clone, Copy, Debug, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    #[default]
+    Srgb, // Encoded with the sRGB curve in 8-bit images, linear in float ones
+    LinearSrgb,
+    Acescg, // Linear with AP1 primaries, for compositing in ACES pipelines
+}
+
+impl ColorSpace {
+    fn convert(self, color: Color) -> Color {
+        match self {
+            ColorSpace::Srgb | ColorSpace::LinearSrgb => color,
+            ColorSpace::Acescg => mat3_transform(&REC709_TO_AP1, color),
+        }
+    }
+
+    fn encode(self, value: f32) -> f32 {
+        match self {
+            ColorSpace::Srgb => linear_to_srgb(value),
+            ColorSpace::LinearSrgb | ColorSpace::Acescg => value,
+        }
+    }
+}
+
+// Writes the framebuffer in the color space, as floats for .exr and .hdr files and 8 bits per
+// channel otherwise
+pub fn save(path: &Path, framebuffer: &[Color], width: u32, height: u32, color_space: ColorSpace) {
+    let is_float = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr") || ext.eq_ignore_ascii_case("hdr"));
+    let colors = framebuffer.iter().map(|&color| color_space.convert(color));
+
+    if is_float {
+        let data = colors.flat_map(|c| [c.x, c.y, c.z]).collect();
+        Rgb32FImage::from_raw(width, height, data)
+            .expect("Framebuffer size doesn't match the image")
+            .save(path)
+            .unwrap();
+    } else {
+        let data = colors
+            .flat_map(|c| [c.x, c.y, c.z])
+            .map(|value| (255.0 * color_space.encode(value).clamp(0.0, 1.0)).round() as u8)
+            .collect();
+        RgbImage::from_raw(width, height, data)
+            .expect("Framebuffer size doesn't match the image")
+            .save(path)
+            .unwrap();
+    }
+}
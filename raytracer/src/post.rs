@@ -0,0 +1,270 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use util::{Color, color::mat3_transform};
+
+const SRGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175_0],
+    [0.019_333_9, 0.119_192, 0.950_304_1],
+];
+const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266, 1.876_010_8, 0.041_556_0],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+// Cone responses that white balancing scales, and back
+const BRADFORD: [[f32; 3]; 3] = [
+    [0.895_1, 0.266_4, -0.161_4],
+    [-0.750_2, 1.713_5, 0.036_7],
+    [0.038_9, -0.068_5, 1.029_6],
+];
+const BRADFORD_INVERSE: [[f32; 3]; 3] = [
+    [0.986_992_9, -0.147_054_3, 0.159_962_7],
+    [0.432_305_3, 0.518_360_3, 0.049_291_2],
+    [-0.008_528_7, 0.040_042_8, 0.968_486_7],
+];
+
+// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms
+const ACES_INPUT: [[f32; 3]; 3] = [
+    [0.597_19, 0.354_58, 0.048_23],
+    [0.076_00, 0.908_34, 0.015_66],
+    [0.028_40, 0.133_83, 0.837_77],
+];
+const ACES_OUTPUT: [[f32; 3]; 3] = [
+    [1.604_75, -0.531_08, -0.073_67],
+    [-0.102_08, 1.108_13, -0.006_05],
+    [-0.003_27, -0.072_76, 1.076_02],
+];
+
+// Troy Sobotka's AgX with the default look, as fitted by Benjamin Wrensch
+const AGX_INSET: [[f32; 3]; 3] = [
+    [0.842_479_06, 0.078_433_6, 0.079_223_745],
+    [0.042_328_242, 0.878_468_6, 0.079_166_13],
+    [0.042_375_655, 0.078_433_6, 0.879_143],
+];
+const AGX_OUTSET: [[f32; 3]; 3] = [
+    [1.196_879, -0.098_020_88, -0.099_029_74],
+    [-0.052_896_85, 1.151_903_1, -0.098_961_18],
+    [-0.052_971_635, -0.098_043_45, 1.151_073_7],
+];
+const AGX_MIN_EV: f32 = -12.473_93;
+const AGX_MAX_EV: f32 = 4.026_069;
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapper {
+    #[default]
+    None, // Clips whatever is brighter than white
+    Reinhard,
+    Aces,
+    Agx,
+    PbrNeutral, // Khronos PBR Neutral, which keeps base colors true up to where highlights start
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PostSettings {
+    pub exposure: f32,              // In stops, each doubling the brightness
+    pub white_balance: Option<f32>, // Kelvin of the light that should come out white
+    pub tone_mapper: ToneMapper,
+}
+
+// Turns the rendered radiance into display values, still linear and with sRGB primaries
+pub fn apply(settings: &PostSettings, framebuffer: &mut [Color]) {
+    let scale = settings.exposure.exp2();
+    let gains = settings.white_balance.map(white_balance_gains);
+
+    for color in framebuffer {
+        let mut c = *color * scale;
+        if let Some(gains) = gains {
+            let cone = mat3_transform(&BRADFORD, mat3_transform(&SRGB_TO_XYZ, c)) * gains;
+            c = mat3_transform(&XYZ_TO_SRGB, mat3_transform(&BRADFORD_INVERSE, cone));
+        }
+        *color = settings.tone_mapper.map(c);
+    }
+}
+
+impl ToneMapper {
+    fn map(self, color: Color) -> Color {
+        match self {
+            ToneMapper::None => color,
+            ToneMapper::Reinhard => Color::new(
+                color.x / (1.0 + color.x),
+                color.y / (1.0 + color.y),
+                color.z / (1.0 + color.z),
+            ),
+            ToneMapper::Aces => {
+                let fit = |v: f32| {
+                    let a = v * (v + 0.024_578_6) - 0.000_090_537;
+                    let b = v * (0.983_729 * v + 0.432_951) + 0.238_081;
+                    a / b
+                };
+                let c = mat3_transform(&ACES_INPUT, color);
+                mat3_transform(&ACES_OUTPUT, Color::new(fit(c.x), fit(c.y), fit(c.z)))
+            }
+            ToneMapper::Agx => {
+                let curve = |v: f32| {
+                    let v = (v.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV) - AGX_MIN_EV)
+                        / (AGX_MAX_EV - AGX_MIN_EV);
+                    let (v2, v4) = (v * v, v * v * v * v);
+                    15.5 * v4 * v2 - 40.14 * v4 * v + 31.96 * v4 - 6.868 * v2 * v
+                        + 0.4298 * v2
+                        + 0.1191 * v
+                        - 0.002_32
+                };
+                let c = mat3_transform(&AGX_INSET, color);
+                // The curve ends in display encoded values, which the 2.2 gamma takes back to linear
+                let c = mat3_transform(&AGX_OUTSET, Color::new(curve(c.x), curve(c.y), curve(c.z)));
+                Color::new(
+                    c.x.max(0.0).powf(2.2),
+                    c.y.max(0.0).powf(2.2),
+                    c.z.max(0.0).powf(2.2),
+                )
+            }
+            ToneMapper::PbrNeutral => pbr_neutral(color),
+        }
+    }
+}
+
+// Reference implementation from the Khronos PBR Neutral specification
+fn pbr_neutral(color: Color) -> Color {
+    const START_COMPRESSION: f32 = 0.8 - 0.04;
+    const DESATURATION: f32 = 0.15;
+
+    let x = color.x.min(color.y).min(color.z);
+    let offset = if x < 0.08 { x - 6.25 * x * x } else { 0.04 };
+    let color = color - Color::new(offset, offset, offset);
+
+    let peak = color.x.max(color.y).max(color.z);
+    if peak < START_COMPRESSION {
+        return color;
+    }
+    let d = 1.0 - START_COMPRESSION;
+    let new_peak = 1.0 - d * d / (peak + d - START_COMPRESSION);
+    let color = color * (new_peak / peak);
+
+    let g = 1.0 - 1.0 / (DESATURATION * (peak - new_peak) + 1.0);
+    color * (1.0 - g) + Color::new(new_peak, new_peak, new_peak) * g
+}
+
+// Von Kries scaling in Bradford cone space, from the white of the temperature to D65, the white
+// of sRGB. Daylight whites lie on the CIE daylight locus, warmer ones on the Planckian locus
+fn white_balance_gains(kelvin: f32) -> Color {
+    let t = kelvin.clamp(1667.0, 25000.0);
+    let (t2, t3) = (t * t, t * t * t);
+    let (x, y) = if t >= 4000.0 {
+        let x = if t <= 7000.0 {
+            -4.6070e9 / t3 + 2.9678e6 / t2 + 0.09911e3 / t + 0.244_063
+        } else {
+            -2.0064e9 / t3 + 1.9018e6 / t2 + 0.24748e3 / t + 0.237_040
+        };
+        (x, -3.0 * x * x + 2.870 * x - 0.275)
+    } else {
+        let x = -0.266_123_9e9 / t3 - 0.234_358_9e6 / t2 + 0.877_695_6e3 / t + 0.179_910;
+        let y = if t <= 2222.0 {
+            -1.106_381_4 * x * x * x - 1.348_110_2 * x * x + 2.185_558_3 * x - 0.202_196_83
+        } else {
+            -0.954_947_6 * x * x * x - 1.374_185_9 * x * x + 2.091_37 * x - 0.167_488_67
+        };
+        (x, y)
+    };
+
+    let source = mat3_transform(&BRADFORD, Color::new(x / y, 1.0, (1.0 - x - y) / y));
+    let target = mat3_transform(
+        &BRADFORD,
+        mat3_transform(&SRGB_TO_XYZ, Color::new(1.0, 1.0, 1.0)),
+    );
+    Color::new(
+        target.x / source.x,
+        target.y / source.y,
+        target.z / source.z,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Color, b: Color, tolerance: f32) {
+        assert!(
+            (a.x - b.x).abs() < tolerance
+                && (a.y - b.y).abs() < tolerance
+                && (a.z - b.z).abs() < tolerance,
+            "{a:?} is not {b:?}"
+        );
+    }
+
+    #[test]
+    fn white_balance_of_d65_keeps_white() {
+        assert_close(white_balance_gains(6504.0), Color::new(1.0, 1.0, 1.0), 0.01);
+    }
+
+    #[test]
+    fn white_balance_of_warm_light_cools_it_down() {
+        let gains = white_balance_gains(3000.0);
+        assert!(gains.z > 1.0 && gains.x < 1.0, "{gains:?}");
+    }
+
+    #[test]
+    fn default_settings_leave_colors_alone() {
+        let mut framebuffer = [Color::new(0.25, 1.5, 4.0)];
+        apply(&PostSettings::default(), &mut framebuffer);
+        assert_close(framebuffer[0], Color::new(0.25, 1.5, 4.0), 1e-6);
+    }
+
+    #[test]
+    fn exposure_doubles_per_stop() {
+        let settings = PostSettings {
+            exposure: 2.0,
+            ..PostSettings::default()
+        };
+        let mut framebuffer = [Color::new(0.25, 0.5, 1.0)];
+        apply(&settings, &mut framebuffer);
+        assert_close(framebuffer[0], Color::new(1.0, 2.0, 4.0), 1e-6);
+    }
+
+    #[test]
+    fn reinhard_maps_one_to_half() {
+        let c = ToneMapper::Reinhard.map(Color::new(1.0, 0.0, 3.0));
+        assert_close(c, Color::new(0.5, 0.0, 0.75), 1e-6);
+    }
+
+    #[test]
+    fn pbr_neutral_matches_reference_values() {
+        // Below the compression only the toe offset applies
+        assert_close(
+            pbr_neutral(Color::new(0.5, 0.5, 0.5)),
+            Color::new(0.46, 0.46, 0.46),
+            1e-6,
+        );
+        assert_close(
+            pbr_neutral(Color::new(0.04, 0.04, 0.04)),
+            Color::new(0.01, 0.01, 0.01),
+            1e-6,
+        );
+        assert_close(
+            pbr_neutral(Color::new(1.0, 1.0, 1.0)),
+            Color::new(0.869_091, 0.869_091, 0.869_091),
+            1e-5,
+        );
+    }
+
+    #[test]
+    fn curves_keep_grays_gray_and_below_white() {
+        for tone_mapper in [ToneMapper::Aces, ToneMapper::Agx, ToneMapper::PbrNeutral] {
+            let mut previous = 0.0;
+            for value in [0.01, 0.18, 1.0, 10.0, 1000.0] {
+                let c = tone_mapper.map(Color::new(value, value, value));
+                assert!(
+                    (c.x - c.y).abs() < 0.01 && (c.y - c.z).abs() < 0.01,
+                    "{tone_mapper:?} tints {value}: {c:?}"
+                );
+                // Hill's ACES fit overshoots white by a little
+                assert!(
+                    c.y > previous && c.y < 1.02,
+                    "{tone_mapper:?} at {value}: {c:?}"
+                );
+                previous = c.y;
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use util::sampler::{
+    HaltonSampler, IndependentSampler, SamplerType, SobolSampler, StratifiedSampler,
+};
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    #[default]
+    Sobol,
+}
+
+impl SamplerKind {
+    // Each tile creates its own sampler. Samples only depend on the seed, pixel and sample index,
+    // so renders with the same seed are identical regardless of thread count or scheduling.
+    pub fn create(self, samples_per_pixel: u32, seed: u64) -> SamplerType {
+        match self {
+            SamplerKind::Independent => SamplerType::Independent(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => {
+                SamplerType::Stratified(StratifiedSampler::new(samples_per_pixel, seed))
+            }
+            SamplerKind::Halton => SamplerType::Halton(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => SamplerType::Sobol(SobolSampler::new(seed)),
+        }
+    }
+}
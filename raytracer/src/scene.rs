@@ -0,0 +1,637 @@
+use std::path::{Path, PathBuf};
+
+use geometry::{Bounds, Hittable, HittableType, trs_matrix};
+use light::{
+    DirectionalLight, EnvironmentLight, Light, LightType, PointLight, PreethamSky, SpotLight,
+    sun_direction,
+};
+use material::{
+    Conductor, ConductorPreset, Density, DensityGrid, Emission, Interface, Material, MaterialType,
+    Medium, Subsurface, Texture,
+};
+use parser::{parse_glb, parse_gltf, parse_obj, parse_vol};
+use serde::Deserialize;
+use util::{Color, Vec3, quat::from_axis_angle};
+
+use crate::{
+    camera::{Background, CameraSettings},
+    filter::FilterType,
+    output::ColorSpace,
+    post::{PostSettings, ToneMapper},
+    sampler::SamplerKind,
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    #[serde(default = "default_output")]
+    pub output: PathBuf,
+    #[serde(default)]
+    pub color_space: ColorSpace,
+    #[serde(default)]
+    pub post_processing: PostConfig,
+    #[serde(default)]
+    pub background: BackgroundConfig,
+    #[serde(default)]
+    pub camera: CameraConfig,
+    pub objects: Vec<ObjectConfig>,
+    #[serde(default)]
+    pub lights: Vec<LightConfig>,
+    #[serde(default)]
+    pub materials: Vec<MaterialConfig>,
+    // Homogeneous fog filling the scene's bounding box
+    pub medium: Option<MediumConfig>,
+    // Model paths are resolved relative to the scene file
+    #[serde(skip)]
+    base_path: PathBuf,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundConfig {
+    #[default]
+    Gradient,
+    Black,
+    Color([f64; 3]),
+    // Equirectangular .hdr or .exr image, rotated around +Y by degrees
+    Environment {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: f32,
+        #[serde(default = "default_environment_intensity")]
+        intensity: f32,
+    },
+    // Preetham sky with the sun at the given degrees above the horizon and clockwise from -Z
+    Sky {
+        sun_elevation: f32,
+        #[serde(default)]
+        sun_azimuth: f32,
+        #[serde(default = "default_turbidity")]
+        turbidity: f32,
+        #[serde(default = "default_environment_intensity")]
+        intensity: f32,
+    },
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    pub look_from: Option<[f64; 3]>,
+    pub look_at: Option<[f64; 3]>,
+    pub up: Option<[f64; 3]>,
+    pub vfov: Option<f32>,
+    pub aspect_ratio: Option<f32>,
+    pub image_width: Option<u32>,
+    pub image_height: Option<u32>,
+    pub samples_per_pixel: Option<u32>,
+    pub defocus_angle: Option<f32>,
+    pub focus_distance: Option<f32>,
+    pub autofocus: Option<bool>,
+    pub filter: Option<FilterType>,
+    pub sampler: Option<SamplerKind>,
+    pub seed: Option<u64>,
+    pub rr_depth: Option<u32>,
+    pub max_diffuse_depth: Option<u32>,
+    pub max_glossy_depth: Option<u32>,
+    pub max_transmission_depth: Option<u32>,
+    pub max_volume_depth: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostConfig {
+    pub exposure: Option<f32>,
+    pub white_balance: Option<f32>,
+    pub tone_mapper: Option<ToneMapper>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectConfig {
+    pub path: PathBuf,
+    pub scale: Option<[f64; 3]>,
+    pub rotate: Option<RotateConfig>,
+    pub translate: Option<[f64; 3]>,
+    // Of the object's transmissive materials, to nest them in each other like liquid in a glass
+    pub priority: Option<u32>,
+    // Fills the closed object, which turns invisible unless it's transmissive
+    pub medium: Option<MediumConfig>,
+}
+
+// Coefficients are per unit of distance, g is the Henyey-Greenstein asymmetry
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MediumConfig {
+    #[serde(default)]
+    pub sigma_a: [f64; 3],
+    #[serde(default)]
+    pub sigma_s: [f64; 3],
+    #[serde(default)]
+    pub g: f32,
+    // Mitsuba .vol grid scaling the coefficients, stretched over the object's bounding box
+    pub density: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RotateConfig {
+    pub axis: [f64; 3],
+    pub degrees: f32,
+}
+
+// Angles are in degrees, intensities in the same units as emissive materials
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum LightConfig {
+    Point {
+        position: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: [f64; 3],
+        intensity: f32,
+    },
+    Spot {
+        position: [f64; 3],
+        direction: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: [f64; 3],
+        intensity: f32,
+        #[serde(default)]
+        inner_angle: f32,
+        #[serde(default = "default_outer_angle")]
+        outer_angle: f32,
+    },
+    Directional {
+        direction: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: [f64; 3],
+        intensity: f32,
+    },
+}
+
+// Replaces every material of the same name loaded from the model files
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialConfig {
+    // Metal from a preset, with its index of refraction per channel overridden by eta and k
+    Conductor {
+        name: String,
+        preset: Option<String>,
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+        #[serde(default)]
+        roughness: f32,
+    },
+    // Translucent material whose color comes from light scattering around under the surface
+    Subsurface {
+        name: String,
+        albedo: [f64; 3],
+        mean_free_path: [f64; 3],
+        #[serde(default = "default_subsurface_ior")]
+        ior: f32,
+        #[serde(default)]
+        roughness: f32,
+        #[serde(default)]
+        g: f32,
+    },
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> Self {
+        let file = std::fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("Failed to read scene file {}", path.display()));
+        let mut scene: Scene = serde_json::from_str(&file)
+            .unwrap_or_else(|e| panic!("Failed to parse scene file {}: {e}", path.display()));
+
+        scene.base_path = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        scene
+    }
+
+    // Lights from model files are moved along with their objects. Media are indexed by the
+    // interiors of the materials bounding them
+    #[allow(clippy::type_complexity)]
+    pub fn load_objects(
+        &self,
+    ) -> (
+        Vec<HittableType>,
+        Vec<MaterialType>,
+        Vec<LightType>,
+        Vec<Medium>,
+    ) {
+        let mut objects = vec![];
+        let mut materials = vec![];
+        let mut lights = vec![];
+        let mut media = vec![];
+        let mut replaced = vec![false; self.materials.len()];
+
+        for object in &self.objects {
+            // Material indices of each file are shifted past the ones already loaded
+            let mat_offset = materials.len();
+            let (mut new_objects, mut new_materials, mut new_lights) =
+                self.load_model(&object.path, mat_offset);
+
+            // Replaced before the object's priority and medium are wired into them
+            for (config, replaced) in self.materials.iter().zip(&mut replaced) {
+                for material in &mut new_materials {
+                    if material.get_name() == config.name() {
+                        let emission = take_emission(material);
+                        *material = match MaterialType::from(config) {
+                            MaterialType::Conductor(conductor) => {
+                                MaterialType::Conductor(Conductor {
+                                    emission,
+                                    ..conductor
+                                })
+                            }
+                            MaterialType::Subsurface(subsurface) => {
+                                MaterialType::Subsurface(subsurface.with_emission(emission))
+                            }
+                            other => other,
+                        };
+                        *replaced = true;
+                    }
+                }
+            }
+
+            if let Some(priority) = object.priority {
+                for material in &mut new_materials {
+                    match material {
+                        MaterialType::Dielectric(dielectric) => dielectric.set_priority(priority),
+                        MaterialType::Subsurface(subsurface) => subsurface.set_priority(priority),
+                        _ => {}
+                    }
+                }
+            }
+
+            for obj in &mut new_objects {
+                object.apply_transform(obj);
+            }
+
+            if let Some(config) = &object.medium {
+                let medium = self.load_medium(config, Some(&Bounds::from(&new_objects)));
+                for material in &mut new_materials {
+                    if let MaterialType::Dielectric(dielectric) = material {
+                        dielectric.set_medium(media.len());
+                    } else {
+                        *material = MaterialType::Interface(Interface {
+                            name: material.get_name().to_owned(),
+                            medium: media.len(),
+                            priority: object.priority.unwrap_or(0),
+                        });
+                    }
+                }
+                media.push(medium);
+            }
+
+            for light in &mut new_lights {
+                light.transform(object.transform_matrix());
+            }
+
+            objects.extend(new_objects);
+            materials.extend(new_materials);
+            lights.extend(new_lights);
+        }
+
+        for (config, replaced) in self.materials.iter().zip(replaced) {
+            assert!(replaced, "No material named {} to replace", config.name());
+        }
+
+        for material in &mut materials {
+            if let MaterialType::Subsurface(subsurface) = material {
+                subsurface.set_medium(media.len());
+                media.push(subsurface.medium());
+            }
+        }
+
+        (objects, materials, lights, media)
+    }
+
+    pub fn load_scene_medium(&self) -> Option<Medium> {
+        self.medium.as_ref().map(|config| {
+            assert!(
+                config.density.is_none(),
+                "A density grid needs an object to fill"
+            );
+            self.load_medium(config, None)
+        })
+    }
+
+    fn load_medium(&self, config: &MediumConfig, bounds: Option<&Bounds>) -> Medium {
+        let density = match (&config.density, bounds) {
+            (Some(path), Some(bounds)) => {
+                let full_path = self.base_path.join(path);
+                let path_str = full_path.to_str().expect("Volume path is not valid UTF-8");
+                let (data, size) = parse_vol(path_str);
+                Density::Grid(DensityGrid::new(data, size, bounds.min, bounds.max))
+            }
+            _ => Density::Uniform,
+        };
+        Medium::new(
+            Color::from(config.sigma_a),
+            Color::from(config.sigma_s),
+            config.g,
+            density,
+        )
+    }
+
+    pub fn load_background(&self) -> Background {
+        match &self.background {
+            BackgroundConfig::Gradient => Background::Gradient,
+            BackgroundConfig::Black => Background::Solid(Color::zero()),
+            BackgroundConfig::Color(color) => Background::Solid(Color::from(color)),
+            BackgroundConfig::Environment {
+                path,
+                rotation,
+                intensity,
+            } => Background::Environment(EnvironmentLight::load(
+                &self.base_path.join(path),
+                rotation.to_radians(),
+                *intensity,
+            )),
+            BackgroundConfig::Sky {
+                sun_elevation,
+                sun_azimuth,
+                turbidity,
+                intensity,
+            } => {
+                let sky = PreethamSky::new(sun_direction(*sun_elevation, *sun_azimuth), *turbidity);
+                Background::Sky {
+                    sky: sky.environment(*intensity),
+                    sun: sky.sun(*intensity),
+                }
+            }
+        }
+    }
+
+    pub fn load_lights(&self) -> Vec<LightType> {
+        self.lights.iter().map(LightType::from).collect()
+    }
+
+    fn load_model(
+        &self,
+        path: &Path,
+        mat_offset: usize,
+    ) -> (Vec<HittableType>, Vec<MaterialType>, Vec<LightType>) {
+        let full_path = self.base_path.join(path);
+        let path_str = full_path.to_str().expect("Model path is not valid UTF-8");
+
+        match full_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase)
+        {
+            Some(ref ext) if ext == "glb" => parse_glb(path_str, mat_offset),
+            Some(ref ext) if ext == "gltf" => parse_gltf(path_str, mat_offset),
+            Some(ref ext) if ext == "obj" => {
+                let (objects, materials) = parse_obj(path_str, mat_offset);
+                (objects, materials, vec![])
+            }
+            other => panic!("Unknown model file extension: {other:?}"),
+        }
+    }
+}
+
+impl ObjectConfig {
+    // Scale, then rotate, then translate
+    fn apply_transform(&self, object: &mut HittableType) {
+        if let Some(scale) = self.scale {
+            object.scale(&Vec3::from(scale));
+        }
+
+        if let Some(RotateConfig { axis, degrees }) = self.rotate {
+            let axis = Vec3::from(axis).normalize();
+            object.rotate(&Vec3::new(axis.x, axis.y, axis.z), degrees.to_radians());
+        }
+
+        if let Some(translate) = self.translate {
+            object.translate(&Vec3::from(translate));
+        }
+    }
+
+    // The same scale, rotate, translate order as a single matrix
+    fn transform_matrix(&self) -> [[f64; 4]; 4] {
+        let scale = self.scale.map_or(Vec3::from(1.0), Vec3::from);
+        let rotation = self.rotate.as_ref().map(|RotateConfig { axis, degrees }| {
+            let axis = Vec3::from(*axis).normalize();
+            from_axis_angle(Vec3::new(axis.x, axis.y, axis.z), degrees.to_radians())
+        });
+        trs_matrix(self.translate.map(Vec3::from), rotation, scale)
+    }
+}
+
+impl CameraConfig {
+    pub fn apply(&self, settings: &mut CameraSettings) {
+        if let Some(look_from) = self.look_from {
+            settings.look_from = look_from.into();
+        }
+        if let Some(look_at) = self.look_at {
+            settings.look_at = look_at.into();
+        }
+        if let Some(up) = self.up {
+            settings.up = up.into();
+        }
+        if let Some(vfov) = self.vfov {
+            settings.vfov = vfov;
+        }
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            settings.aspect_ratio = aspect_ratio;
+        }
+        if let Some(image_width) = self.image_width {
+            settings.image_width = image_width;
+        }
+        if self.image_height.is_some() {
+            settings.image_height = self.image_height;
+        }
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            settings.samples_per_pixel = samples_per_pixel;
+        }
+        if let Some(defocus_angle) = self.defocus_angle {
+            settings.defocus_angle = defocus_angle;
+        }
+        if self.focus_distance.is_some() {
+            settings.focus_distance = self.focus_distance;
+        }
+        if let Some(autofocus) = self.autofocus {
+            settings.autofocus = autofocus;
+        }
+        if let Some(filter) = self.filter {
+            settings.filter = filter;
+        }
+        if let Some(sampler) = self.sampler {
+            settings.sampler = sampler;
+        }
+        if self.seed.is_some() {
+            settings.seed = self.seed;
+        }
+        if let Some(rr_depth) = self.rr_depth {
+            settings.rr_depth = rr_depth;
+        }
+        if let Some(max_diffuse_depth) = self.max_diffuse_depth {
+            settings.max_diffuse_depth = max_diffuse_depth;
+        }
+        if let Some(max_glossy_depth) = self.max_glossy_depth {
+            settings.max_glossy_depth = max_glossy_depth;
+        }
+        if let Some(max_transmission_depth) = self.max_transmission_depth {
+            settings.max_transmission_depth = max_transmission_depth;
+        }
+        if let Some(max_volume_depth) = self.max_volume_depth {
+            settings.max_volume_depth = max_volume_depth;
+        }
+    }
+}
+
+impl PostConfig {
+    pub fn apply(&self, settings: &mut PostSettings) {
+        if let Some(exposure) = self.exposure {
+            settings.exposure = exposure;
+        }
+        if self.white_balance.is_some() {
+            settings.white_balance = self.white_balance;
+        }
+        if let Some(tone_mapper) = self.tone_mapper {
+            settings.tone_mapper = tone_mapper;
+        }
+    }
+}
+
+impl MaterialConfig {
+    fn name(&self) -> &str {
+        match self {
+            MaterialConfig::Conductor { name, .. } | MaterialConfig::Subsurface { name, .. } => {
+                name
+            }
+        }
+    }
+}
+
+// Overrides only change how a material scatters, so whatever it emits carries over
+fn take_emission(material: &mut MaterialType) -> Option<Emission> {
+    match material {
+        MaterialType::Lambertian(lambertian) => lambertian.emission.take(),
+        MaterialType::TextureLambertian(lambertian) => lambertian.emission.take(),
+        MaterialType::Emissive(emissive) => {
+            Some(Emission::new(emissive.color * emissive.intensity, None))
+        }
+        MaterialType::Dielectric(dielectric) => dielectric.take_emission(),
+        MaterialType::Conductor(conductor) => conductor.emission.take(),
+        MaterialType::Interface(_) => None,
+        MaterialType::Subsurface(subsurface) => subsurface.take_emission(),
+    }
+}
+
+impl From<&MaterialConfig> for MaterialType {
+    fn from(config: &MaterialConfig) -> Self {
+        match config {
+            MaterialConfig::Conductor {
+                name,
+                preset,
+                eta,
+                k,
+                roughness,
+            } => {
+                let (preset_eta, preset_k) = preset.as_ref().map_or_else(
+                    || {
+                        assert!(
+                            eta.is_some() && k.is_some(),
+                            "Conductor {name} needs a preset or both eta and k"
+                        );
+                        (Color::zero(), Color::zero())
+                    },
+                    |preset| {
+                        preset
+                            .parse::<ConductorPreset>()
+                            .unwrap_or_else(|e| panic!("{e}"))
+                            .eta_k()
+                    },
+                );
+                MaterialType::Conductor(Conductor {
+                    name: name.clone(),
+                    eta: eta.map_or(preset_eta, Color::from),
+                    k: k.map_or(preset_k, Color::from),
+                    roughness: *roughness,
+                    emission: None,
+                })
+            }
+            MaterialConfig::Subsurface {
+                name,
+                albedo,
+                mean_free_path,
+                ior,
+                roughness,
+                g,
+            } => MaterialType::Subsurface(Subsurface::new(
+                name.clone(),
+                Texture::constant(Color::from(albedo)),
+                Color::from(mean_free_path),
+                *ior,
+                *roughness,
+                *g,
+            )),
+        }
+    }
+}
+
+impl From<&LightConfig> for LightType {
+    fn from(config: &LightConfig) -> Self {
+        match *config {
+            LightConfig::Point {
+                position,
+                color,
+                intensity,
+            } => LightType::Point(PointLight {
+                position: position.into(),
+                color: color.into(),
+                intensity,
+                range: None,
+            }),
+            LightConfig::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                inner_angle,
+                outer_angle,
+            } => LightType::Spot(SpotLight::new(
+                position.into(),
+                Vec3::from(direction).normalize(),
+                color.into(),
+                intensity,
+                inner_angle.to_radians(),
+                outer_angle.to_radians(),
+                None,
+            )),
+            LightConfig::Directional {
+                direction,
+                color,
+                intensity,
+            } => LightType::Directional(DirectionalLight {
+                direction: Vec3::from(direction).normalize(),
+                color: color.into(),
+                intensity,
+            }),
+        }
+    }
+}
+
+fn default_light_color() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_outer_angle() -> f32 {
+    45.0
+}
+
+fn default_environment_intensity() -> f32 {
+    1.0
+}
+
+fn default_subsurface_ior() -> f32 {
+    1.4
+}
+
+fn default_turbidity() -> f32 {
+    3.0
+}
+
+fn default_output() -> PathBuf {
+    PathBuf::from("output.png")
+}
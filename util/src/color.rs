@@ -0,0 +1,35 @@
+use std::sync::LazyLock;
+
+use crate::Color;
+
+// Linear values of the 256 sRGB encoded 8-bit levels, which is all textures come in
+static SRGB_TO_LINEAR: LazyLock<[f32; 256]> =
+    LazyLock::new(|| std::array::from_fn(|i| srgb_to_linear(f32::from(i as u8) / 255.0)));
+
+// sRGB transfer function, from an encoded value in [0, 1] to linear light
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Inverse of srgb_to_linear, for writing linear light to 8-bit images
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_u8_to_linear(value: u8) -> f32 {
+    SRGB_TO_LINEAR[usize::from(value)]
+}
+
+// Color times a row-major 3x3 matrix, for moving between color spaces
+pub fn mat3_transform(m: &[[f32; 3]; 3], color: Color) -> Color {
+    let row = |r: [f32; 3]| r[0] * color.x + r[1] * color.y + r[2] * color.z;
+    Color::new(row(m[0]), row(m[1]), row(m[2]))
+}
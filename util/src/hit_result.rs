@@ -6,7 +6,15 @@ pub struct HitResult {
     pub t: f32,
     pub point: Vec3,
     pub material_index: Option<usize>,
+    pub tex_coords: [TexCoord; 2], // TEXCOORD_0 and _1, the first one twice without a second set
+    pub front_face: bool,
+    pub object_id: u64, // The mesh, sphere or instance the surface is part of
+    pub footprint: (Vec3, Vec3), // World space half axes of the area a camera ray covers, or zero
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TexCoord {
     pub u: f32,
     pub v: f32,
-    pub front_face: bool,
+    pub gradients: (Vec3, Vec3), // Change of u and v per unit of world distance along the surface
 }
@@ -1,10 +1,12 @@
+pub mod color;
 mod hit_result;
 mod interval;
 pub mod quat;
 mod ray;
+pub mod sampler;
 mod vec3;
 
-pub use hit_result::HitResult;
+pub use hit_result::{HitResult, TexCoord};
 pub use interval::Interval;
 pub use ray::Ray;
-pub use vec3::{Color, Normalized, Point, THREAD_RNG, Unnormalized, Vec3};
+pub use vec3::{Color, Normalized, Point, Unnormalized, Vec3};
@@ -1,5 +1,5 @@
 use crate::{
-    Point,
+    Interval, Point,
     vec3::{Normalized, Vec3},
 };
 
@@ -22,4 +22,19 @@ impl Ray {
     pub fn at(&self, dst: f32) -> Point {
         self.origin + self.dir * dst
     }
+
+    // Part of the interval where the ray is inside the box between min and max
+    pub fn box_interval(&self, min: &Point, max: &Point, interval: &Interval) -> Option<Interval> {
+        let (mut t_min, mut t_max) = (interval.min, interval.max);
+        for (origin, dir, min, max) in [
+            (self.origin.x, self.dir.x, min.x, max.x),
+            (self.origin.y, self.dir.y, min.y, max.y),
+            (self.origin.z, self.dir.z, min.z, max.z),
+        ] {
+            let (t0, t1) = ((min - origin) / dir, (max - origin) / dir);
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+        }
+        (t_min < t_max).then_some(Interval::new(t_min, t_max))
+    }
 }
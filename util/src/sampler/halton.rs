@@ -0,0 +1,88 @@
+#![allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+
+use crate::sampler::{
+    Sampler,
+    hash::{ONE_MINUS_EPSILON, hash, mix_bits, permutation_element, random_float},
+};
+
+// Dimensions past the last prime fall back to hashed random values
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+// Halton sequence with Owen-scrambled digits, independently scrambled for every pixel
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    seed: u64,
+    pixel_hash: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel_hash: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn sample_dimension(&self, dimension: u32) -> f32 {
+        let h = hash(&[self.pixel_hash, u64::from(dimension)]);
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => owen_scrambled_radical_inverse(base, self.sample_index, h as u32),
+            None => random_float(&[h, u64::from(self.sample_index)]),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample_index: u32) {
+        self.pixel_hash = hash(&[u64::from(pixel.0), u64::from(pixel.1), self.seed]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let value = self.sample_dimension(self.dimension);
+        self.dimension += 1;
+        value
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let value = (
+            self.sample_dimension(self.dimension),
+            self.sample_dimension(self.dimension + 1),
+        );
+        self.dimension += 2;
+        value
+    }
+}
+
+// Mirrors the base-b digits of `index` about the radix point, permuting each digit based on
+// the digits that precede it
+fn owen_scrambled_radical_inverse(base: u32, index: u32, seed: u32) -> f32 {
+    let inv_base = 1.0 / f64::from(base);
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits: u64 = 0;
+    let mut remaining = index;
+
+    // Continue until the digits no longer affect an f32
+    while 1.0 - (inv_base_m as f32) < 1.0 {
+        let next = remaining / base;
+        let digit = remaining - next * base;
+        let digit_hash = mix_bits(u64::from(seed) ^ reversed_digits) as u32;
+        let digit = permutation_element(digit, base, digit_hash);
+
+        reversed_digits = reversed_digits * u64::from(base) + u64::from(digit);
+        inv_base_m *= inv_base;
+        remaining = next;
+    }
+
+    ((reversed_digits as f64 * inv_base_m) as f32).min(ONE_MINUS_EPSILON)
+}
@@ -0,0 +1,65 @@
+// Largest f32 below one
+pub const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+// 64-bit finalizer from MurmurHash3
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 33;
+    v = v.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    v ^= v >> 33;
+    v = v.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    v ^= v >> 33;
+    v
+}
+
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |h, &v| {
+        mix_bits(h.rotate_left(23) ^ v)
+    })
+}
+
+// Maps the top 24 bits onto [0, 1)
+#[allow(clippy::cast_precision_loss)]
+pub fn to_unit_float(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
+
+pub fn random_float(values: &[u64]) -> f32 {
+    to_unit_float((hash(values) >> 32) as u32)
+}
+
+// Element i of a random permutation of 0..len chosen by `seed` (Kensler 2013)
+pub fn permutation_element(mut i: u32, len: u32, seed: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < len {
+            break;
+        }
+    }
+
+    i.wrapping_add(seed) % len
+}
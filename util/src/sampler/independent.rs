@@ -0,0 +1,39 @@
+use rand::{RngExt, SeedableRng, rngs::SmallRng};
+
+use crate::sampler::{Sampler, hash::hash};
+
+// Uniform random samples with no stratification across dimensions or samples
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    seed: u64,
+    rng: SmallRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    // Reseeding per pixel sample makes the sequence independent of the order pixels are visited
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample_index: u32) {
+        self.rng = SmallRng::seed_from_u64(hash(&[
+            u64::from(pixel.0),
+            u64::from(pixel.1),
+            u64::from(sample_index),
+            self.seed,
+        ]));
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.rng.random::<f32>()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.rng.random::<f32>(), self.rng.random::<f32>())
+    }
+}
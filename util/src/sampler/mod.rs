@@ -0,0 +1,137 @@
+mod halton;
+mod hash;
+mod independent;
+mod sobol;
+mod stratified;
+
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
+
+// Supplies the random numbers for one pixel sample at a time. Each call consumes the next
+// dimension so that every decision along a path gets its own well-distributed value.
+pub trait Sampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample_index: u32);
+    fn get_1d(&mut self) -> f32;
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+#[derive(Debug, Clone)]
+pub enum SamplerType {
+    Independent(IndependentSampler),
+    Stratified(StratifiedSampler),
+    Halton(HaltonSampler),
+    Sobol(SobolSampler),
+}
+
+impl Sampler for SamplerType {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample_index: u32) {
+        match self {
+            SamplerType::Independent(sampler) => sampler.start_pixel_sample(pixel, sample_index),
+            SamplerType::Stratified(sampler) => sampler.start_pixel_sample(pixel, sample_index),
+            SamplerType::Halton(sampler) => sampler.start_pixel_sample(pixel, sample_index),
+            SamplerType::Sobol(sampler) => sampler.start_pixel_sample(pixel, sample_index),
+        }
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        match self {
+            SamplerType::Independent(sampler) => sampler.get_1d(),
+            SamplerType::Stratified(sampler) => sampler.get_1d(),
+            SamplerType::Halton(sampler) => sampler.get_1d(),
+            SamplerType::Sobol(sampler) => sampler.get_1d(),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        match self {
+            SamplerType::Independent(sampler) => sampler.get_2d(),
+            SamplerType::Stratified(sampler) => sampler.get_2d(),
+            SamplerType::Halton(sampler) => sampler.get_2d(),
+            SamplerType::Sobol(sampler) => sampler.get_2d(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+
+    use super::*;
+
+    const SAMPLES: u32 = 16;
+
+    fn samplers() -> [SamplerType; 4] {
+        [
+            SamplerType::Independent(IndependentSampler::new(7)),
+            SamplerType::Stratified(StratifiedSampler::new(SAMPLES, 7)),
+            SamplerType::Halton(HaltonSampler::new(7)),
+            SamplerType::Sobol(SobolSampler::new(7)),
+        ]
+    }
+
+    // The first values of every sample of a pixel, in 1d then 2d
+    fn first_values(sampler: &mut SamplerType, pixel: (u32, u32)) -> Vec<(f32, (f32, f32))> {
+        (0..SAMPLES)
+            .map(|sample_index| {
+                sampler.start_pixel_sample(pixel, sample_index);
+                (sampler.get_1d(), sampler.get_2d())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn values_are_in_unit_interval() {
+        for mut sampler in samplers() {
+            for sample_index in 0..SAMPLES {
+                sampler.start_pixel_sample((3, 5), sample_index);
+                for _ in 0..100 {
+                    let value = sampler.get_1d();
+                    let (x, y) = sampler.get_2d();
+                    assert!(
+                        [value, x, y].iter().all(|v| (0.0..1.0).contains(v)),
+                        "{sampler:?} gave {value}, {x}, {y}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samples_only_depend_on_pixel_and_index() {
+        for mut sampler in samplers() {
+            let first = first_values(&mut sampler, (3, 5));
+            first_values(&mut sampler, (4, 5));
+            assert_eq!(first, first_values(&mut sampler, (3, 5)), "{sampler:?}");
+            assert_ne!(first, first_values(&mut sampler, (4, 5)), "{sampler:?}");
+        }
+    }
+
+    #[test]
+    fn low_discrepancy_samplers_fill_every_stratum() {
+        for mut sampler in samplers().into_iter().skip(1) {
+            let values = first_values(&mut sampler, (3, 5));
+            let mut strata = [0; SAMPLES as usize];
+            for (value, _) in &values {
+                strata[(value * SAMPLES as f32) as usize] += 1;
+            }
+            assert_eq!(strata, [1; SAMPLES as usize], "{sampler:?} in 1d");
+
+            // Halton's second and third dimensions are in base 3 and 5, which a 4x4 grid
+            // doesn't split evenly
+            if matches!(sampler, SamplerType::Halton(_)) {
+                continue;
+            }
+            let mut cells = [0; SAMPLES as usize];
+            for (_, (x, y)) in &values {
+                cells[(y * 4.0) as usize * 4 + (x * 4.0) as usize] += 1;
+            }
+            assert_eq!(cells, [1; SAMPLES as usize], "{sampler:?} in 2d");
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::sampler::{
+    Sampler,
+    hash::{hash, to_unit_float},
+};
+
+// Owen-scrambled Sobol points, padded from the first two Sobol dimensions. Every request gets
+// its own scramble and sample order, following Burley's "Practical Hash-based Owen Scrambling".
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    seed: u64,
+    pixel_hash: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel_hash: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    // Shuffles the sample order for this dimension and returns the scramble seeds
+    fn next_dimension(&mut self) -> (u32, u32, u32) {
+        let h = hash(&[self.pixel_hash, u64::from(self.dimension)]);
+        self.dimension += 1;
+
+        let index = nested_uniform_scramble(self.sample_index, h as u32);
+        let seed = hash(&[h]);
+        (index, seed as u32, (seed >> 32) as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample_index: u32) {
+        self.pixel_hash = hash(&[u64::from(pixel.0), u64::from(pixel.1), self.seed]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (index, seed, _) = self.next_dimension();
+        to_unit_float(nested_uniform_scramble(index.reverse_bits(), seed))
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let (index, seed_x, seed_y) = self.next_dimension();
+        (
+            to_unit_float(nested_uniform_scramble(index.reverse_bits(), seed_x)),
+            to_unit_float(nested_uniform_scramble(
+                sobol_second_dimension(index),
+                seed_y,
+            )),
+        )
+    }
+}
+
+// The second Sobol dimension; its generator matrix is Pascal's triangle mod 2
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
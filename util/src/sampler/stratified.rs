@@ -0,0 +1,69 @@
+#![allow(clippy::cast_precision_loss, clippy::cast_sign_loss)]
+
+use crate::sampler::{
+    Sampler,
+    hash::{ONE_MINUS_EPSILON, hash, permutation_element, random_float},
+};
+
+// Jittered strata per dimension. Each dimension visits the strata in its own random order so
+// that dimensions stay decorrelated.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    strata_per_axis: u32, // 2D samples use a square grid of strata
+    seed: u64,
+    pixel_hash: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        Self {
+            samples_per_pixel,
+            strata_per_axis: (samples_per_pixel as f32).sqrt().ceil() as u32,
+            seed,
+            pixel_hash: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next_dimension_hash(&mut self) -> u64 {
+        let h = hash(&[self.pixel_hash, u64::from(self.dimension)]);
+        self.dimension += 1;
+        h
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), sample_index: u32) {
+        self.pixel_hash = hash(&[u64::from(pixel.0), u64::from(pixel.1), self.seed]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let h = self.next_dimension_hash();
+        let count = self.samples_per_pixel;
+        let stratum = permutation_element(self.sample_index % count, count, h as u32);
+        let jitter = random_float(&[h, u64::from(self.sample_index)]);
+
+        ((stratum as f32 + jitter) / count as f32).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let h = self.next_dimension_hash();
+        let n = self.strata_per_axis;
+        let count = n * n;
+        let stratum = permutation_element(self.sample_index % count, count, h as u32);
+        let jitter_x = random_float(&[h, u64::from(self.sample_index), 0]);
+        let jitter_y = random_float(&[h, u64::from(self.sample_index), 1]);
+
+        (
+            (((stratum % n) as f32 + jitter_x) / n as f32).min(ONE_MINUS_EPSILON),
+            (((stratum / n) as f32 + jitter_y) / n as f32).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
@@ -1,4 +1,3 @@
-use rand::{RngExt, SeedableRng, rngs::SmallRng};
 use std::marker::PhantomData;
 use std::ops;
 
@@ -34,6 +33,11 @@ impl<S> Vec3<S> {
         }
     }
 
+    // Relative luminance of a linear Rec. 709 color
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+
     pub fn length_squared(&self) -> f32 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
@@ -55,22 +59,6 @@ impl<S> Vec3<S> {
         self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
     }
 
-    pub fn random_in_unit_sphere() -> Vec3<Unnormalized> {
-        THREAD_RNG.with(|thread_rng| {
-            let mut thread_rng = thread_rng.borrow_mut();
-            loop {
-                let p = Vec3::new(
-                    thread_rng.random::<f32>() * 2.0 - 1.0,
-                    thread_rng.random::<f32>() * 2.0 - 1.0,
-                    thread_rng.random::<f32>() * 2.0 - 1.0,
-                );
-                if p.length_squared() < 1.0 {
-                    return p;
-                }
-            }
-        })
-    }
-
     pub fn dot<T>(&self, other: &Vec3<T>) -> f32 {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
@@ -103,11 +91,6 @@ impl<S> Vec3<S> {
     }
 }
 
-thread_local! {
-    pub static THREAD_RNG: std::cell::RefCell<SmallRng> =
-        std::cell::RefCell::new(SmallRng::from_rng(&mut rand::rng()));
-}
-
 impl<S, T> ops::Add<Vec3<T>> for Vec3<S> {
     type Output = Vec3<Unnormalized>;
 
@@ -213,6 +196,37 @@ impl<S> ops::Div<f32> for Vec3<S> {
 }
 
 impl Vec3<Unnormalized> {
+    // Uniformly distributed point on the unit sphere
+    pub fn sample_unit_sphere(u: (f32, f32)) -> Self {
+        let z = 1.0 - 2.0 * u.0;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * u.1;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    // Uniformly distributed point inside the unit sphere
+    pub fn sample_in_unit_sphere(u: (f32, f32), u_radius: f32) -> Self {
+        Vec3::sample_unit_sphere(u) * u_radius.cbrt()
+    }
+
+    // Uniformly distributed point inside the unit disk on the XY plane (concentric mapping)
+    pub fn sample_in_unit_disk(u: (f32, f32)) -> Self {
+        let (x, y) = (u.0 * 2.0 - 1.0, u.1 * 2.0 - 1.0);
+        if x == 0.0 && y == 0.0 {
+            return Self::new(0.0, 0.0, 0.0);
+        }
+
+        let (r, theta) = if x.abs() > y.abs() {
+            (x, std::f32::consts::FRAC_PI_4 * (y / x))
+        } else {
+            (
+                y,
+                std::f32::consts::FRAC_PI_2 - std::f32::consts::FRAC_PI_4 * (x / y),
+            )
+        };
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+    }
+
     #[must_use]
     pub fn normalize(&self) -> Vec3<Normalized> {
         let len_squared = self.length_squared();